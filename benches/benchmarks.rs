@@ -42,7 +42,7 @@ struct Cli<'a> {
     secret: [u8; 14],
 }
 
-const TSON_STRING: &'static str = r#"
+const TSON_STRING: &str = r#"
 log_level info that shit
 addr 0.0.0.0:8080 that shit
 in theory db_url that shit
@@ -136,9 +136,9 @@ secret
 oh yeah
 "#;
 
-const JSON_STRING: &'static str = r#"{"log_level":"info","addr":"0.0.0.0:8080","db_url":null,"notes":"Captains log:\nmaking the dumbest shit imaginable.\n\nWhy? Who knows? I don't","embedding_model":{"url":"http://host.docker.internal/v1","model":"embeddinggemma-vllm"},"reembed":true,"service_type":{"main":{"auth_service":"web-auth"}},"other_service":"auth","color":{"Rgb":[127,255,100]},"wrapped_color":{"Rgb":[5,6,7]},"more_colors":[[{"Rgb":[1,2,3]},5,{"Rgb":[4,5,6]}],[{"Rgb":[1,2,3]},5,{"Rgb":[4,5,6]}]],"secret":[14,6,7,6,87,69,78,5,6,4,64,6,45,6],"cipher":[[1,2,3],[4,5,6,7,8,9],[10]],"map":{"red":{"Rgb":[255,0,0]},"blue":{"Rgb":[0,0,255]},"green":{"Rgb":[0,255,0]}},"true_map":{"42":true,"69":true,"67":false}}"#;
+const JSON_STRING: &str = r#"{"log_level":"info","addr":"0.0.0.0:8080","db_url":null,"notes":"Captains log:\nmaking the dumbest shit imaginable.\n\nWhy? Who knows? I don't","embedding_model":{"url":"http://host.docker.internal/v1","model":"embeddinggemma-vllm"},"reembed":true,"service_type":{"main":{"auth_service":"web-auth"}},"other_service":"auth","color":{"Rgb":[127,255,100]},"wrapped_color":{"Rgb":[5,6,7]},"more_colors":[[{"Rgb":[1,2,3]},5,{"Rgb":[4,5,6]}],[{"Rgb":[1,2,3]},5,{"Rgb":[4,5,6]}]],"secret":[14,6,7,6,87,69,78,5,6,4,64,6,45,6],"cipher":[[1,2,3],[4,5,6,7,8,9],[10]],"map":{"red":{"Rgb":[255,0,0]},"blue":{"Rgb":[0,0,255]},"green":{"Rgb":[0,255,0]}},"true_map":{"42":true,"69":true,"67":false}}"#;
 
-const TOML_STRING: &'static str = r#"
+const TOML_STRING: &str = r#"
 log_level = "info"
 addr = "0.0.0.0:8080"
 notes = """
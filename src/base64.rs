@@ -0,0 +1,49 @@
+/// Byte blocks are written as a single word of standard, padded base64 after
+/// this
+pub(crate) const PREFIX: &str = "base64:";
+
+const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+pub(crate) fn encode(bytes: &[u8]) -> String {
+    let mut encoded = String::with_capacity(PREFIX.len() + bytes.len().div_ceil(3) * 4);
+    encoded.push_str(PREFIX);
+    for chunk in bytes.chunks(3) {
+        let group = chunk.iter().enumerate().fold(0u32, |group, (i, byte)| {
+            group | u32::from(*byte) << (16 - 8 * i)
+        });
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(group >> (18 - 6 * i)) as usize & 0x3f] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+/// Decodes a `base64:...` word, or returns `None` if it isn't one
+pub(crate) fn decode(text: &str) -> Option<Vec<u8>> {
+    let encoded = text.strip_prefix(PREFIX)?.as_bytes();
+    if encoded.len() % 4 != 0 {
+        return None;
+    }
+
+    let mut bytes = Vec::with_capacity(encoded.len() / 4 * 3);
+    for (i, chunk) in encoded.chunks(4).enumerate() {
+        let last = i == encoded.len() / 4 - 1;
+        let padding = chunk.iter().rev().take_while(|c| **c == b'=').count();
+        if padding > 2 || (padding > 0 && !last) {
+            return None;
+        }
+        let mut group = 0u32;
+        for c in &chunk[..4 - padding] {
+            let sextet = ALPHABET.iter().position(|a| a == c)?;
+            group = group << 6 | sextet as u32;
+        }
+        group <<= 6 * padding;
+        let group = group.to_be_bytes();
+        bytes.extend_from_slice(&group[1..4 - padding]);
+    }
+    Some(bytes)
+}
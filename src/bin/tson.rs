@@ -0,0 +1,240 @@
+use std::{
+    fs,
+    io::{self, Read},
+    path::Path,
+    process::ExitCode,
+};
+
+use tysonscript_object_notation::{self as tson, Value};
+
+const USAGE: &str = "\
+usage: tson fmt [--check] [FILE]...
+       tson check [FILE]...
+       tson convert [--from FORMAT] [--to FORMAT] [FILE]
+
+Reads stdin when no file is given.
+
+fmt      rewrites files in the layout the serializer writes, with entries
+         sorted by key, in the dialect they are written in. Files holding
+         comments, or numbers that would be written differently, are left
+         alone with a note. With --check, only lists the files that would
+         change
+check    reports every file that fails to parse
+convert  translates between tson, json and toml, writing to stdout. --from is
+         guessed from the file extension, and both default to tson
+
+Exits with 1 when a file fails to parse or is not formatted, and 2 on misuse";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Tson,
+    Json,
+    Toml,
+}
+
+impl Format {
+    fn from_name(name: &str) -> Option<Self> {
+        match name {
+            "tson" => Some(Self::Tson),
+            "json" => Some(Self::Json),
+            "toml" => Some(Self::Toml),
+            _ => None,
+        }
+    }
+
+    fn read(self, source: &str) -> Result<Value, String> {
+        match self {
+            Self::Tson => tson::from_str(source).map_err(|err| err.to_string()),
+            Self::Json => serde_json::from_str(source).map_err(|err| err.to_string()),
+            Self::Toml => toml::from_str(source).map_err(|err| err.to_string()),
+        }
+    }
+
+    fn write(self, value: &Value) -> Result<String, String> {
+        match self {
+            Self::Tson => tson::to_string(value).map_err(|err| err.to_string()),
+            Self::Json => serde_json::to_string_pretty(value)
+                .map(|json| json + "\n")
+                .map_err(|err| err.to_string()),
+            Self::Toml => toml::to_string(value).map_err(|err| err.to_string()),
+        }
+    }
+}
+
+/// A file to read, or stdin
+struct Input<'a>(Option<&'a str>);
+
+impl Input<'_> {
+    fn all(files: &[String]) -> Vec<Input<'_>> {
+        if files.is_empty() {
+            return vec![Input(None)];
+        }
+        files.iter().map(|file| Input(Some(file))).collect()
+    }
+
+    fn name(&self) -> &str {
+        self.0.unwrap_or("<stdin>")
+    }
+
+    fn read(&self) -> Result<String, String> {
+        let result = match self.0 {
+            Some(file) => fs::read_to_string(file),
+            None => {
+                let mut source = String::new();
+                io::stdin().read_to_string(&mut source).map(|_| source)
+            }
+        };
+        result.map_err(|err| format!("{}: {err}", self.name()))
+    }
+
+    /// Parses the input, reporting why it could not be
+    fn parse(&self, format: Format) -> Option<(String, Value)> {
+        let parsed = self.read().and_then(|source| {
+            let value = format
+                .read(source.strip_prefix('\u{feff}').unwrap_or(&source))
+                .map_err(|err| format!("{}: {err}", self.name()))?;
+            Ok((source, value))
+        });
+        parsed.map_err(|err| eprintln!("{err}")).ok()
+    }
+}
+
+/// The outcome of a command: whether every input was fine, or a usage error
+type Outcome = Result<bool, String>;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let outcome = match args.split_first() {
+        Some((command, args)) => match command.as_str() {
+            "fmt" => fmt(args),
+            "check" => check(args),
+            "convert" => convert(args),
+            "help" | "-h" | "--help" => {
+                println!("{USAGE}");
+                Ok(true)
+            }
+            command => Err(format!("unknown command `{command}`")),
+        },
+        None => Err(String::from("missing command")),
+    };
+
+    match outcome {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::from(1),
+        Err(message) => {
+            eprintln!("tson: {message}\n\n{USAGE}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+fn fmt(args: &[String]) -> Outcome {
+    let (check, files) = match args.split_first() {
+        Some((flag, files)) if flag == "--check" => (true, files),
+        _ => (false, args),
+    };
+    reject_flags(files)?;
+
+    let mut ok = true;
+    for input in Input::all(files) {
+        let formatted = input.read().and_then(|source| {
+            let formatted =
+                tson::dialect::format(&source).map_err(|err| format!("{}: {err}", input.name()))?;
+            Ok((source, formatted))
+        });
+        let (source, formatted) = match formatted {
+            Ok((source, Some(formatted))) => (source, formatted),
+            Ok((source, None)) => {
+                eprintln!(
+                    "{}: left alone, as rewriting it would lose comments or how numbers are written",
+                    input.name()
+                );
+                if !check && input.0.is_none() {
+                    print!("{source}");
+                }
+                continue;
+            }
+            Err(err) => {
+                eprintln!("{err}");
+                ok = false;
+                continue;
+            }
+        };
+
+        if check {
+            if formatted != source {
+                println!("{}", input.name());
+                ok = false;
+            }
+        } else if let Some(file) = input.0 {
+            if formatted != source
+                && let Err(err) = fs::write(file, formatted)
+            {
+                eprintln!("{file}: {err}");
+                ok = false;
+            }
+        } else {
+            print!("{formatted}");
+        }
+    }
+    Ok(ok)
+}
+
+fn check(files: &[String]) -> Outcome {
+    reject_flags(files)?;
+    let mut ok = true;
+    for input in Input::all(files) {
+        ok &= input.parse(Format::Tson).is_some();
+    }
+    Ok(ok)
+}
+
+fn convert(mut args: &[String]) -> Outcome {
+    let mut from = None;
+    let mut to = Format::Tson;
+    while let [flag, name, rest @ ..] = args
+        && (flag == "--from" || flag == "--to")
+    {
+        let format = Format::from_name(name).ok_or_else(|| format!("unknown format `{name}`"))?;
+        if flag == "--from" {
+            from = Some(format);
+        } else {
+            to = format;
+        }
+        args = rest;
+    }
+    reject_flags(args)?;
+    let file = match args {
+        [] => None,
+        [file] => Some(file.as_str()),
+        _ => return Err(String::from("convert takes at most one file")),
+    };
+
+    let from = from
+        .or_else(|| {
+            let extension = Path::new(file?).extension()?.to_str()?;
+            Format::from_name(extension)
+        })
+        .unwrap_or(Format::Tson);
+    let input = Input(file);
+    let Some((_, value)) = input.parse(from) else {
+        return Ok(false);
+    };
+    match to.write(&value) {
+        Ok(output) => {
+            print!("{output}");
+            Ok(true)
+        }
+        Err(err) => {
+            eprintln!("{}: {err}", input.name());
+            Ok(false)
+        }
+    }
+}
+
+fn reject_flags(files: &[String]) -> Result<(), String> {
+    match files.iter().find(|file| file.starts_with("--")) {
+        Some(flag) => Err(format!("unknown option `{flag}`")),
+        None => Ok(()),
+    }
+}
@@ -1,23 +1,32 @@
-use std::{fmt::Display, iter::Peekable};
+use std::{borrow::Cow, fmt::Display, iter::Peekable, ops::Range, rc::Rc};
 
 use logos::{Lexer, Logos};
 use serde_core::{
-    Deserializer,
-    de::{self, EnumAccess, Expected, MapAccess, SeqAccess, Unexpected, VariantAccess, Visitor},
+    Deserialize, Deserializer,
+    de::{
+        self, EnumAccess, Expected, MapAccess, SeqAccess, Unexpected, VariantAccess, Visitor,
+        value::BorrowedStrDeserializer,
+    },
 };
 use thiserror::Error;
 
+use crate::{
+    base64,
+    dialect::{Dialect, DialectLexer, TSON},
+    key::{self, KeyDeserializer},
+};
+
 #[derive(Debug, Error)]
-pub enum Error {
+pub enum ErrorKind {
     #[error("invalid type: {0}, expected {1}")]
     InvalidType(String, String),
     #[error("invalid value: {0}, expected {1}")]
     InvalidValue(String, String),
     #[error("invalid length: {0}, expected {1}")]
     InvalidLength(usize, String),
-    #[error("unknown variant `{0}`, expected one of {0:?}")]
+    #[error("unknown variant `{0}`, expected one of {1:?}")]
     UnknownVariant(String, &'static [&'static str]),
-    #[error("unknown field `{0}`, expected one of {0:?}")]
+    #[error("unknown field `{0}`, expected one of {1:?}")]
     UnknownField(String, &'static [&'static str]),
     #[error("missing field `{0}`")]
     MissingField(&'static str),
@@ -27,56 +36,167 @@ pub enum Error {
     UnexpectedEof,
     #[error("Map key must be stringable")]
     KeyMustBeStringable,
+    #[error("Invalid token `{0}`")]
+    InvalidToken(String),
+    #[error("Trailing tokens after the end of the document")]
+    TrailingTokens,
+    #[error("Comments cannot sit inside a value")]
+    CommentInValue,
+    #[error("Invalid dialect: {0}")]
+    InvalidDialect(String),
+    #[error("Source is not valid UTF-8")]
+    Utf8(#[from] std::str::Utf8Error),
+    #[error("An error occurred while reading from reader")]
+    Io(#[from] std::io::Error),
     #[error("{0}")]
     Custom(String),
 }
 
+/// Where in the source an [`Error`] was raised
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Location {
+    /// Byte range of the offending token
+    pub span: Range<usize>,
+    /// 1-based line of the start of `span`
+    pub line: usize,
+    /// 1-based column, counted in characters, of the start of `span`
+    pub column: usize,
+}
+
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    location: Option<Location>,
+}
+
+impl Error {
+    pub fn kind(&self) -> &ErrorKind {
+        &self.kind
+    }
+
+    pub fn into_kind(self) -> ErrorKind {
+        self.kind
+    }
+
+    pub fn location(&self) -> Option<&Location> {
+        self.location.as_ref()
+    }
+
+    pub fn span(&self) -> Option<Range<usize>> {
+        self.location.as_ref().map(|location| location.span.clone())
+    }
+
+    /// Points at the first invalid byte, counting lines and columns over the
+    /// valid text before it
+    #[cold]
+    pub(crate) fn new(kind: ErrorKind, location: Location) -> Self {
+        Error {
+            kind,
+            location: Some(location),
+        }
+    }
+
+    pub(crate) fn utf8(bytes: &[u8], err: std::str::Utf8Error) -> Self {
+        let start = err.valid_up_to();
+        let end = start + err.error_len().unwrap_or(bytes.len() - start);
+        let valid = std::str::from_utf8(&bytes[..start]).unwrap_or_default();
+        Error {
+            location: Some(Location::new(valid, start..end)),
+            kind: ErrorKind::Utf8(err),
+        }
+    }
+}
+
+impl Location {
+    pub(crate) fn new(source: &str, span: Range<usize>) -> Self {
+        let before = &source[..span.start];
+        let line_start = before.rfind('\n').map_or(0, |newline| newline + 1);
+        Location {
+            line: before.matches('\n').count() + 1,
+            column: before[line_start..].chars().count() + 1,
+            span,
+        }
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Error {
+            kind,
+            location: None,
+        }
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.location {
+            Some(location) => write!(
+                f,
+                "{} at line {}, column {}",
+                self.kind, location.line, location.column
+            ),
+            None => write!(f, "{}", self.kind),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
 impl de::Error for Error {
     #[cold]
     fn invalid_type(unexp: Unexpected, exp: &dyn Expected) -> Self {
-        Error::InvalidType(unexp.to_string(), exp.to_string())
+        ErrorKind::InvalidType(unexp.to_string(), exp.to_string()).into()
     }
 
     #[cold]
     fn invalid_value(unexp: Unexpected, exp: &dyn Expected) -> Self {
-        Error::InvalidValue(unexp.to_string(), exp.to_string())
+        ErrorKind::InvalidValue(unexp.to_string(), exp.to_string()).into()
     }
 
     #[cold]
     fn invalid_length(len: usize, exp: &dyn Expected) -> Self {
-        Error::InvalidLength(len, exp.to_string())
+        ErrorKind::InvalidLength(len, exp.to_string()).into()
     }
 
     #[cold]
     fn unknown_variant(variant: &str, expected: &'static [&'static str]) -> Self {
-        Error::UnknownVariant(variant.to_owned(), expected)
+        ErrorKind::UnknownVariant(variant.to_owned(), expected).into()
     }
 
     #[cold]
     fn unknown_field(field: &str, expected: &'static [&'static str]) -> Self {
-        Error::UnknownField(field.to_owned(), expected)
+        ErrorKind::UnknownField(field.to_owned(), expected).into()
     }
 
     #[cold]
     fn missing_field(field: &'static str) -> Self {
-        Error::MissingField(field)
+        ErrorKind::MissingField(field).into()
     }
 
     #[cold]
     fn duplicate_field(field: &'static str) -> Self {
-        Error::DuplicateField(field)
+        ErrorKind::DuplicateField(field).into()
     }
 
     fn custom<T>(msg: T) -> Self
     where
         T: Display,
     {
-        Error::Custom(msg.to_string())
+        ErrorKind::Custom(msg.to_string()).into()
     }
 }
 
 #[derive(Logos, Debug, PartialEq, Clone, Copy)]
 #[logos(skip(r"((fuckin)?[ \t\r\n\f]+)+"))]
+// Comments: `psst` to the end of the line, or anything from `shut the fuck up`
+// to `go on`
+#[logos(skip(r"psst([ \t\r\f][^\n]*)?", priority = 10))]
+#[logos(skip(
+    r"shut[ \t\r\n\f]+the[ \t\r\n\f]+fuck[ \t\r\n\f]+up",
+    callback = block_comment,
+    priority = 10
+))]
 pub enum Token<'source> {
     // Prefixes
     #[token("dont")]
@@ -90,115 +210,543 @@ pub enum Token<'source> {
     #[regex(r"oh[ \t\r\n\f]+(fuckin[ \t\r\n\f]+)?yeah")]
     OhYeah,
 
-    #[regex(r"[^ \t\r\n\f]+")]
+    /// Text taken verbatim from between `#"` and `"#`, where any number of
+    /// `#`s may be used as long as the closing run matches the opening one
+    #[regex(r##"#+""##, raw_text)]
+    Raw(&'source str),
+
+    #[regex(r##"[^ \t\r\n\f#][^ \t\r\n\f]*|#+([^ \t\r\n\f#"][^ \t\r\n\f]*)?"##)]
     Text(&'source str),
 }
 
-impl<'source> Display for Token<'source> {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+fn raw_text<'source>(lex: &mut Lexer<'source, Token<'source>>) -> Option<&'source str> {
+    let hashes = lex.slice().len() - 1;
+    let remainder = lex.remainder();
+    let mut search = 0;
+    while let Some(quote) = remainder[search..].find('"') {
+        let end = search + quote;
+        let closing = &remainder.as_bytes()[end + 1..];
+        if closing.len() >= hashes && closing[..hashes].iter().all(|b| *b == b'#') {
+            lex.bump(end + 1 + hashes);
+            return Some(&remainder[..end]);
+        }
+        search = end + 1;
+    }
+
+    None
+}
+
+/// Skips past the `go on` closing a block comment, failing if there is none
+fn block_comment<'source>(lex: &mut Lexer<'source, Token<'source>>) -> Result<(), ()> {
+    let remainder = lex.remainder();
+    for (go, _) in remainder.match_indices("go") {
+        if !remainder[..go].ends_with(is_space) {
+            continue;
+        }
+        let after = &remainder[go + 2..];
+        let on = after.trim_start_matches(is_space);
+        if on.len() == after.len() || !on.starts_with("on") {
+            continue;
+        }
+        let end = remainder.len() - on.len() + 2;
+        if remainder[end..].starts_with(|c: char| !is_space(c)) {
+            continue;
+        }
+        lex.bump(end);
+        return Ok(());
+    }
+
+    Err(())
+}
+
+/// Whitespace as the lexer skips it
+pub(crate) fn is_space(c: char) -> bool {
+    matches!(c, ' ' | '\t' | '\r' | '\n' | '\x0c')
+}
+
+/// Whether a string has to be written as raw text to be read back unchanged
+pub(crate) fn needs_raw(text: &str, dialect: Option<&Dialect>) -> bool {
+    let mut end = 0;
+    let mut last = "";
+    for (words, (token, span)) in TsonLexer::in_dialect(text, dialect).enumerate() {
+        let Ok(Token::Text(word)) = token else {
+            return true;
+        };
+        match words {
+            // Leading whitespace or intensifiers would be skipped
+            0 if span.start != 0 => return true,
+            // A single word on the first line reads back as a variant block
+            1 if text[end..span.start].contains('\n') => return true,
+            _ => {}
+        }
+        end = span.end;
+        last = word;
+    }
+
+    // Trailing whitespace, or an intensifier that would be read as part of
+    // `that shit`, would be lost too, and sequences written on one line would
+    // be read as such
+    text.is_empty()
+        || end != text.len()
+        || last == dialect.unwrap_or(&TSON).fuckin
+        || text.starts_with('[')
+}
+
+/// Whether text read without a type comes back as a unit or a number rather
+/// than a string, so that a string holding it has to be written as raw text
+pub(crate) fn reads_as_scalar(text: &str) -> bool {
+    text == "unit"
+        || u128::parse_literal(text).is_some()
+        || i128::parse_literal(text).is_some()
+        || f64::parse_literal(text).is_some()
+}
+
+/// Whether a key or variant name has to be written as raw text to be read
+/// back as a single identifier
+pub(crate) fn needs_raw_identifier(text: &str, dialect: Option<&Dialect>) -> bool {
+    let mut lexer = TsonLexer::in_dialect(text, dialect);
+    let single_word = matches!(lexer.next(), Some((Ok(Token::Text(word)), _)) if word.len() == text.len())
+        && lexer.next().is_none();
+
+    // These would pair up with whatever follows them into a keyword, or be
+    // skipped altogether
+    let dialect = dialect.unwrap_or(&TSON);
+    let starts_keyword = [
+        &dialect.that_shit,
+        &dialect.oh_yeah,
+        &dialect.in_theory,
+        &dialect.shut_the_fuck_up,
+    ]
+    .iter()
+    .any(|keyword| keyword.split(' ').next() == Some(text));
+    !single_word || starts_keyword || text == dialect.fuckin
+}
+
+/// Wraps text in as many `#`s as it takes to read it back as raw text
+pub(crate) fn raw(text: &str) -> String {
+    let hashes = "#".repeat(raw_hashes(text));
+    format!("{hashes}\"{text}\"{hashes}")
+}
+
+/// The number of `#`s needed to write `text` as raw text
+pub(crate) fn raw_hashes(text: &str) -> usize {
+    let mut hashes = 1;
+    for (quote, _) in text.match_indices('"') {
+        let run = text[quote + 1..].bytes().take_while(|b| *b == b'#').count();
+        hashes = hashes.max(run + 1);
+    }
+    hashes
+}
+
+impl Token<'_> {
+    /// The token as it is written in a dialect, for error messages
+    pub(crate) fn in_dialect(&self, dialect: &Dialect) -> String {
         match *self {
-            Self::ThatShit => write!(f, "that shit"),
-            Self::Dont => write!(f, "dont"),
-            Self::OhYeah => write!(f, "oh yeah"),
-            Self::InTheory => write!(f, "in theory"),
-            Self::Text(text) => write!(f, "{}", text),
+            Self::ThatShit => dialect.that_shit.to_string(),
+            Self::Dont => dialect.dont.to_string(),
+            Self::OhYeah => dialect.oh_yeah.to_string(),
+            Self::InTheory => dialect.in_theory.to_string(),
+            Self::Raw(text) => format!("#\"{}\"#", text),
+            Self::Text(text) => text.to_owned(),
         }
     }
 }
 
-pub struct TsonLexer<'source>(Lexer<'source, Token<'source>>);
+/// Writes the token as it is in TSON
+impl<'source> Display for Token<'source> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.in_dialect(&TSON))
+    }
+}
+
+#[derive(Clone)]
+pub struct TsonLexer<'source>(Lexers<'source>);
+
+/// TSON gets the lexer generated for its keywords, and every other dialect
+/// one that looks its keywords up as it goes
+#[derive(Clone)]
+enum Lexers<'source> {
+    Tson(Lexer<'source, Token<'source>>),
+    Dialect(DialectLexer<'source>),
+}
 
 impl<'source> Iterator for TsonLexer<'source> {
-    type Item = Token<'source>;
+    type Item = (Result<Token<'source>, ()>, Range<usize>);
 
-    #[cfg(not(debug_assertions))]
     fn next(&mut self) -> Option<Self::Item> {
-        Some(unsafe { self.0.next()?.unwrap_unchecked() })
+        match &mut self.0 {
+            Lexers::Tson(lexer) => {
+                let token = lexer.next()?;
+                Some((token, lexer.span()))
+            }
+            Lexers::Dialect(lexer) => lexer.next(),
+        }
     }
+}
 
-    #[cfg(debug_assertions)]
-    fn next(&mut self) -> Option<Self::Item> {
-        Some(self.0.next()?.unwrap())
+impl<'source> TsonLexer<'source> {
+    pub(crate) fn new(source: &'source str) -> TsonLexer<'source> {
+        TsonLexer(Lexers::Tson(Token::lexer(source)))
+    }
+
+    /// Lexes from `start` on, skipping a `#!tson` line
+    pub(crate) fn with_dialect(source: &'source str, start: usize, dialect: Rc<Dialect>) -> Self {
+        if *dialect == TSON {
+            let mut lexer = Token::lexer(source);
+            lexer.bump(start);
+            return TsonLexer(Lexers::Tson(lexer));
+        }
+        TsonLexer(Lexers::Dialect(DialectLexer::new(source, start, dialect)))
     }
 }
 
-impl<'source> TsonLexer<'source> {
-    fn new(source: &'source str) -> TsonLexer<'source> {
-        TsonLexer(Token::lexer(source))
+/// Checks [`TsonDeserializer`] makes on top of what the format itself needs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DeserializerOptions {
+    non_finite_floats: bool,
+}
+
+impl Default for DeserializerOptions {
+    fn default() -> Self {
+        Self {
+            non_finite_floats: true,
+        }
+    }
+}
+
+impl DeserializerOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Rejects everything only written when a [`crate::SerializerOptions`]
+    /// opts into it
+    pub fn strict() -> Self {
+        Self::new().non_finite_floats(false)
+    }
+
+    /// Whether `inf`, `-inf` and `nan` are read into floats
+    pub fn non_finite_floats(mut self, non_finite_floats: bool) -> Self {
+        self.non_finite_floats = non_finite_floats;
+        self
     }
 }
 
 pub struct TsonDeserializer<'a> {
+    source: &'a str,
     reader: Peekable<TsonLexer<'a>>,
     prefix_token: Option<Token<'a>>,
+    /// Byte offset just past the last token taken from `reader`
+    end: usize,
+    /// Source covered by the last token or value taken, which errors point at
+    span: Range<usize>,
+    /// Set by [`TsonSeqAccess`] when the next value has no key in front of it
+    positional: bool,
+    /// Number of blocks (maps, sequences and variant bodies) currently being
+    /// read, zero at the top of the document
+    depth: usize,
+    dialect: Rc<Dialect>,
+    /// The source covered by every multi-word value read, when asked for by
+    /// [`crate::dialect::translate`]
+    traced: Option<Vec<Range<usize>>>,
+    options: DeserializerOptions,
+    /// Set when the document was found to be a sequence of maps, whose first
+    /// map has no blank line in front of it to set it apart
+    first_map: bool,
 }
 
 impl<'source> TsonDeserializer<'source> {
     pub fn new(str: &'source str) -> Self {
-        let reader = TsonLexer::new(str).peekable();
+        Self::with_dialect(str, 0, Dialect::TSON)
+    }
+
+    /// Reads a document written in `dialect`, starting at `start` so that a
+    /// `#!tson` line can be stepped over
+    pub fn with_dialect(str: &'source str, start: usize, dialect: Dialect) -> Self {
+        let dialect = Rc::new(dialect);
+        let reader = TsonLexer::with_dialect(str, start, Rc::clone(&dialect)).peekable();
         Self {
+            source: str,
             reader,
             prefix_token: None,
+            end: start,
+            span: start..start,
+            positional: false,
+            depth: 0,
+            dialect,
+            traced: None,
+            options: DeserializerOptions::default(),
+            first_map: false,
+        }
+    }
+
+    pub fn with_options(mut self, options: DeserializerOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    pub(crate) fn trace_values(&mut self) {
+        self.traced = Some(Vec::new());
+    }
+
+    pub(crate) fn into_traced_values(self) -> Vec<Range<usize>> {
+        self.traced.unwrap_or_default()
+    }
+
+    /// Checks that nothing but whitespace is left after the top-level value
+    pub fn end(&mut self) -> Result<(), Error> {
+        if self.peek()?.is_none() {
+            return Ok(());
+        }
+        self.next()?;
+        Err(self.error(ErrorKind::TrailingTokens))
+    }
+
+    fn location(&self, span: Range<usize>) -> Location {
+        Location::new(self.source, span)
+    }
+
+    #[cold]
+    fn error(&self, kind: ErrorKind) -> Error {
+        Error {
+            kind,
+            location: Some(self.location(self.span.clone())),
+        }
+    }
+
+    #[cold]
+    fn invalid_token(&self) -> Error {
+        self.error(ErrorKind::InvalidToken(
+            self.source[self.span.clone()].to_owned(),
+        ))
+    }
+
+    #[cold]
+    fn eof(&self) -> Error {
+        let end = self.source.len();
+        Error {
+            kind: ErrorKind::UnexpectedEof,
+            location: Some(self.location(end..end)),
+        }
+    }
+
+    /// Points an error raised outside of the deserializer, such as by a
+    /// visitor, at the last value taken
+    pub(crate) fn locate(&self, mut error: Error) -> Error {
+        if error.location.is_none() {
+            error.location = Some(self.location(self.span.clone()));
         }
+        error
     }
 
     fn next(&mut self) -> Result<Token<'source>, Error> {
-        self.reader.next().ok_or(Error::UnexpectedEof)
+        let Some((token, span)) = self.reader.next() else {
+            return Err(self.eof());
+        };
+        self.end = span.end;
+        self.span = span;
+        token.map_err(|()| self.invalid_token())
+    }
+
+    /// Looks at the next token without taking it, failing if it could not be
+    /// lexed so that it is never mistaken for the end of the document
+    fn peek(&mut self) -> Result<Option<Token<'source>>, Error> {
+        match self.reader.peek() {
+            None => Ok(None),
+            Some((Ok(token), _)) => Ok(Some(*token)),
+            Some((Err(()), span)) => {
+                self.span = span.clone();
+                Err(self.invalid_token())
+            }
+        }
+    }
+
+    /// The skipped whitespace (and intensifiers) between the last token taken
+    /// and the next one
+    fn gap(&mut self) -> &'source str {
+        let start = self
+            .reader
+            .peek()
+            .map_or(self.source.len(), |(_, span)| span.start);
+        &self.source[self.end..start]
+    }
+
+    /// Looks past the next token, returning the one after it and whether a
+    /// line break separates the two
+    fn peek_second(&mut self) -> Option<(Token<'source>, bool)> {
+        let mut ahead = self.reader.clone();
+        let (_, first) = ahead.next()?;
+        let (Ok(token), second) = ahead.next()? else {
+            return None;
+        };
+        Some((token, self.source[first.end..second.start].contains('\n')))
+    }
+
+    /// Whether the next token is a key with `fuckin that shit` on the line
+    /// below it, which is how a sequence starting with `true` is written
+    fn keys_true_seq(&self) -> bool {
+        let mut ahead = self.reader.clone();
+        let Some((_, first)) = ahead.next() else {
+            return false;
+        };
+        let Some((Ok(Token::ThatShit), second)) = ahead.next() else {
+            return false;
+        };
+        let gap = &self.source[first.end..second.start];
+        gap.contains('\n') && leading_intensifiers(gap, &self.dialect.fuckin) != gap.len()
     }
 
     fn that_shit(&mut self) -> Result<(), Error> {
         let next_token = self.next()?;
         if next_token != Token::ThatShit {
-            return Err(Error::InvalidValue(
-                next_token.to_string(),
-                Token::ThatShit.to_string(),
-            ));
+            return Err(self.error(ErrorKind::InvalidValue(
+                next_token.in_dialect(&self.dialect),
+                Token::ThatShit.in_dialect(&self.dialect),
+            )));
         }
         Ok(())
     }
 
+    /// Takes the `dont` or `in theory` in front of a value's key, or in front
+    /// of the value itself when it has no key, as in a sequence element, a
+    /// variant payload or a whole document
+    fn prefix(&mut self) -> Result<Option<Token<'source>>, Error> {
+        if let Some(token) = self.prefix_token.take() {
+            return Ok(Some(token));
+        }
+        if !self.positional && self.depth != 0 {
+            return Ok(None);
+        }
+        // The first element of a nested sequence belongs to that sequence
+        if opens_nested_seq(self.gap(), &self.dialect.fuckin) {
+            return Ok(None);
+        }
+        match self.peek()? {
+            Some(token @ (Token::Dont | Token::InTheory)) => {
+                self.next()?;
+                Ok(Some(token))
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Whether a lone `fuckin` line opens a nested sequence here. If one does,
+    /// `end` is moved past that line, so the gap in front of the sequence's
+    /// first element no longer holds it and that element isn't taken to open
+    /// yet another sequence
+    fn open_nested_seq(&mut self) -> bool {
+        let gap = self.gap();
+        if !opens_nested_seq(gap, &self.dialect.fuckin) {
+            return false;
+        }
+
+        let mut offset = 0;
+        for (i, line) in gap.split_inclusive('\n').enumerate() {
+            offset += line.len();
+            if i > 0 && line.trim() == self.dialect.fuckin {
+                break;
+            }
+        }
+        self.end += offset;
+        true
+    }
+
+    /// The document itself is closed by the end of input, which [`Self::end`]
+    /// checks, so only nested blocks take an `oh yeah`
+    fn close_block(&mut self) -> Result<(), Error> {
+        if self.depth == 0 {
+            return Ok(());
+        }
+        self.oh_yeah_or_none()
+    }
+
     fn oh_yeah_or_none(&mut self) -> Result<(), Error> {
-        let next_token = self.reader.next();
-        let Some(next_token) = next_token else {
+        if self.peek()?.is_none() {
             return Ok(());
-        };
+        }
+        let next_token = self.next()?;
         if next_token != Token::OhYeah {
-            return Err(Error::InvalidValue(
-                next_token.to_string(),
-                Token::OhYeah.to_string(),
-            ));
+            return Err(self.error(ErrorKind::InvalidValue(
+                next_token.in_dialect(&self.dialect),
+                Token::OhYeah.in_dialect(&self.dialect),
+            )));
         }
 
         Ok(())
     }
 
     fn text(&mut self) -> Result<&'source str, Error> {
-        if self.reader.peek() == Some(&Token::ThatShit) {
+        if self.peek()? == Some(Token::ThatShit) {
             return Ok("");
         }
         let next_token = self.next()?;
-        let Token::Text(text) = next_token else {
-            return Err(Error::InvalidValue(
-                next_token.to_string(),
+        let (Token::Text(text) | Token::Raw(text)) = next_token else {
+            return Err(self.error(ErrorKind::InvalidValue(
+                next_token.in_dialect(&self.dialect),
                 String::from("text"),
-            ));
+            )));
         };
         Ok(text)
     }
 
+    /// Takes every token up to and including the next `that shit`, returning
+    /// the source they cover. Intensifiers belong to the value here, so any
+    /// the lexer skipped at either end of it are taken back
+    fn text_run(&mut self) -> Result<&'source str, Error> {
+        let gap = self.gap();
+        let start = self.end + leading_intensifiers(gap, &self.dialect.fuckin);
+        let intensified = start != self.end + gap.len();
+        match self.peek()? {
+            Some(Token::Raw(text)) if !intensified => {
+                self.next()?;
+                let span = self.span.clone();
+                self.that_shit()?;
+                self.span = span;
+                return Ok(text);
+            }
+            Some(Token::ThatShit) if !intensified => {
+                self.next()?;
+                return Ok("");
+            }
+            Some(Token::OhYeah) | None => {
+                self.text()?;
+            }
+            Some(_) => {}
+        }
+
+        let mut end = start;
+        while self.next()? != Token::ThatShit {
+            if end != start
+                && let Some(comment) =
+                    comment_in(&self.source[end..self.span.start], &self.dialect.fuckin)
+            {
+                self.span = end + comment.start..end + comment.end;
+                return Err(self.error(ErrorKind::CommentInValue));
+            }
+            end = self.span.end;
+        }
+        end += trailing_intensifiers(&self.source[end..self.span.start], &self.dialect.fuckin);
+        self.span = start..end;
+        if let Some(traced) = &mut self.traced {
+            traced.push(start..end);
+        }
+
+        Ok(&self.source[start..end])
+    }
+
     fn identifier(&mut self) -> Result<&'source str, Error> {
         let next_token = self.next()?;
         let text = match next_token {
-            Token::Text(text) => text,
+            Token::Text(text) | Token::Raw(text) => text,
             Token::Dont | Token::InTheory => {
                 self.prefix_token = Some(next_token);
                 self.text()?
             }
             token => {
-                return Err(Error::InvalidValue(
-                    token.to_string(),
-                    String::from("text, dont, in theory"),
-                ));
+                return Err(self.error(ErrorKind::InvalidValue(
+                    token.in_dialect(&self.dialect),
+                    format!("text, {}, {}", self.dialect.dont, self.dialect.in_theory),
+                )));
             }
         };
 
@@ -206,18 +754,400 @@ impl<'source> TsonDeserializer<'source> {
     }
 }
 
+/// Hooks for [`crate::document`], which walks the entries of a document
+/// without deserializing them
+impl<'de> TsonDeserializer<'de> {
+    /// Byte offset just past the last token taken
+    pub(crate) fn offset(&self) -> usize {
+        self.end
+    }
+
+    pub(crate) fn dialect(&self) -> &Dialect {
+        &self.dialect
+    }
+
+    /// Source covered by the last token taken
+    pub(crate) fn last_span(&self) -> Range<usize> {
+        self.span.clone()
+    }
+
+    pub(crate) fn peek_spanned(&mut self) -> Result<Option<(Token<'de>, Range<usize>)>, Error> {
+        let Some(token) = self.peek()? else {
+            return Ok(None);
+        };
+        let span = self.reader.peek().map_or(0..0, |(_, span)| span.clone());
+        Ok(Some((token, span)))
+    }
+
+    /// Reads an entry's key along with the `dont` or `in theory` in front of it
+    pub(crate) fn entry_key(&mut self) -> Result<(Option<Token<'de>>, &'de str), Error> {
+        let key = self.identifier()?;
+        Ok((self.prefix_token.take(), key))
+    }
+
+    /// Whether the value after a key is a block of entries of its own, where
+    /// an empty block counts as one
+    pub(crate) fn opens_map(&mut self) -> Result<bool, Error> {
+        if !self.gap().contains('\n') {
+            return Ok(false);
+        }
+        Ok(self.peek()? == Some(Token::OhYeah) || !self.block_is_seq()?)
+    }
+
+    /// Skips the value after a key just as [`Deserializer::deserialize_ignored_any`]
+    /// would
+    pub(crate) fn skip_value(&mut self, prefix: Option<Token<'de>>) -> Result<(), Error> {
+        self.prefix_token = prefix;
+        self.positional = false;
+        de::IgnoredAny::deserialize(&mut *self)?;
+        Ok(())
+    }
+
+    pub(crate) fn open_block(&mut self) {
+        self.depth += 1;
+    }
+
+    pub(crate) fn close_nested_block(&mut self) -> Result<(), Error> {
+        self.depth -= 1;
+        self.oh_yeah_or_none()
+    }
+}
+
+impl<'de> TsonDeserializer<'de> {
+    /// Reads a block of entries closed by `oh yeah` (or the end of the
+    /// document) as a sequence when its first line holds a bare value and as a
+    /// map otherwise
+    fn deserialize_block<V>(&mut self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        if self.block_is_seq()? {
+            self.deserialize_seq(visitor)
+        } else {
+            self.deserialize_map(visitor)
+        }
+    }
+
+    fn block_is_seq(&mut self) -> Result<bool, Error> {
+        Ok(match self.peek()? {
+            None | Some(Token::OhYeah) | Some(Token::ThatShit) => true,
+            Some(Token::Dont) | Some(Token::InTheory) => {
+                matches!(self.peek_second(), Some((Token::ThatShit, _)))
+            }
+            Some(Token::Text(_) | Token::Raw(_)) => {
+                let gap = self.gap();
+                opens_nested_seq(gap, &self.dialect.fuckin)
+                    || (matches!(self.peek_second(), Some((Token::ThatShit, _)))
+                        && !is_intensified(gap, &self.dialect.fuckin)
+                        && !self.keys_true_seq())
+                    || match self.depth {
+                        // A sequence of maps opens with a blank line after its
+                        // key, or at the top of the document, with a line break.
+                        // A document of entries may start with one too, so
+                        // there it takes an `oh yeah` closing the first map
+                        0 => {
+                            self.first_map = gap.contains('\n') && self.top_entries_are_closed();
+                            self.first_map
+                        }
+                        _ => has_blank_line(gap),
+                    }
+            }
+        })
+    }
+
+    /// Whether the entries at the top of the document are closed by an `oh
+    /// yeah`, making them the first map of a sequence rather than the document
+    /// itself. Reads ahead on a copy, so nothing is taken
+    fn top_entries_are_closed(&self) -> bool {
+        let mut ahead = TsonDeserializer {
+            source: self.source,
+            reader: self.reader.clone(),
+            prefix_token: None,
+            end: self.end,
+            span: self.span.clone(),
+            positional: false,
+            depth: 1,
+            dialect: Rc::clone(&self.dialect),
+            traced: None,
+            options: self.options.clone(),
+            first_map: false,
+        };
+        let mut entries = TsonMapAccess {
+            deserializer: &mut ahead,
+        };
+        loop {
+            match entries.next_key::<de::IgnoredAny>() {
+                Ok(Some(_)) if entries.next_value::<de::IgnoredAny>().is_ok() => {}
+                Ok(None) => break,
+                _ => return false,
+            }
+        }
+        matches!(ahead.peek(), Ok(Some(Token::OhYeah)))
+    }
+
+    /// Reads a run of text up to `that shit` as a sequence written on one
+    /// line, a unit, a number, or failing all of those, a string. Byte blocks
+    /// are left as their `base64:` text, as only the type being read knows
+    /// whether it holds bytes or a string
+    fn deserialize_scalar<V>(&mut self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        if let Some(Token::Raw(_)) = self.peek()? {
+            let text = self.text_run()?;
+            return visitor.visit_borrowed_str(text);
+        }
+
+        let text = self.text_run()?;
+        if key::reads_as_list(text, self.options.non_finite_floats) {
+            return self.inline_seq(text, visitor);
+        }
+        visit_scalar(text, self.options.non_finite_floats, visitor)
+    }
+
+    /// Reads the elements of a sequence written on one line as `[a,b]`
+    fn inline_seq<V>(&self, text: &'de str, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        let mut list = KeyDeserializer::new(text, self.options.non_finite_floats);
+        list.deserialize_list(visitor)
+            .and_then(|value| list.end().map(|()| value))
+            .map_err(|err| self.locate(err))
+    }
+}
+
+/// Visits text as a unit, a number, or failing both, a string. `inf` and `nan`
+/// only count as numbers when `non_finite_floats` allows them
+pub(crate) fn visit_scalar<'de, V>(
+    text: &'de str,
+    non_finite_floats: bool,
+    visitor: V,
+) -> Result<V::Value, Error>
+where
+    V: Visitor<'de>,
+{
+    if text == "unit" {
+        return visitor.visit_unit();
+    }
+    if let Some(v) = Literal::parse_literal(text) {
+        return visitor.visit_u64(v);
+    }
+    if let Some(v) = Literal::parse_literal(text) {
+        return visitor.visit_i64(v);
+    }
+    if let Some(v) = Literal::parse_literal(text) {
+        return visitor.visit_u128(v);
+    }
+    if let Some(v) = Literal::parse_literal(text) {
+        return visitor.visit_i128(v);
+    }
+    if let Some(v) = f64::parse_literal(text)
+        && (v.is_finite() || non_finite_floats)
+    {
+        return visitor.visit_f64(v);
+    }
+    visitor.visit_borrowed_str(text)
+}
+
+/// Whether a line consisting of only `fuckin` sits between two tokens, which is
+/// how a sequence nested directly inside another sequence is opened
+fn opens_nested_seq(gap: &str, fuckin: &str) -> bool {
+    let mut lines = gap.split('\n');
+    lines.next();
+    lines.next_back();
+    lines.any(|line| line.trim() == fuckin)
+}
+
+/// Whether an empty line sits between two tokens, as opposed to just a line
+/// break or a comment on a line of its own
+fn has_blank_line(gap: &str) -> bool {
+    let mut lines = gap.split('\n');
+    lines.next();
+    lines.next_back();
+    lines.any(|line| line.trim().is_empty())
+}
+
+/// Where the intensifiers right in front of the next token start, on its own
+/// line, or the end of the gap if there are none
+fn leading_intensifiers(gap: &str, fuckin: &str) -> usize {
+    let line = gap.rfind('\n').map_or(0, |newline| newline + 1);
+    let mut start = gap.len();
+    loop {
+        let before = gap[line..start].trim_end_matches(is_space);
+        match before.strip_suffix(fuckin) {
+            Some(rest) if rest.is_empty() || rest.ends_with(is_space) => start = line + rest.len(),
+            _ => return start,
+        }
+    }
+}
+
+/// Where the intensifiers right after the last token end, or zero if there
+/// are none
+fn trailing_intensifiers(gap: &str, fuckin: &str) -> usize {
+    let mut end = 0;
+    loop {
+        let after = gap[end..].trim_start_matches(is_space);
+        match after.strip_prefix(fuckin) {
+            Some(rest) if rest.is_empty() || rest.starts_with(is_space) => {
+                end = gap.len() - rest.len();
+            }
+            _ => return end,
+        }
+    }
+}
+
+/// Where the comment in the gap between two words of a value sits, if there is
+/// one, as nothing else but whitespace and intensifiers is skipped there
+fn comment_in(gap: &str, fuckin: &str) -> Option<Range<usize>> {
+    let start = gap
+        .split(is_space)
+        .scan(0, |offset, word| {
+            let start = *offset;
+            *offset += word.len() + 1;
+            Some((start, word))
+        })
+        .find(|(_, word)| !word.is_empty() && *word != fuckin)?
+        .0;
+    Some(start..gap.trim_end_matches(is_space).len())
+}
+
+/// Whether a `fuckin` sits on the same line as, and in front of, the next token
+fn is_intensified(gap: &str, fuckin: &str) -> bool {
+    gap.rsplit('\n')
+        .next()
+        .is_some_and(|line| line.contains(fuckin))
+}
+
+/// A number or char as it may be written in a document
+pub(crate) trait Literal: Sized {
+    fn parse_literal(text: &str) -> Option<Self>;
+
+    fn is_finite(&self) -> bool {
+        true
+    }
+}
+
+/// Strips the `_`s separating digits, failing if the text holds anything but
+/// digits of the radix after a digit
+fn digits(text: &str, radix: u32) -> Option<Cow<'_, str>> {
+    if !text.starts_with(|c: char| c.is_digit(radix))
+        || !text.chars().all(|c| c == '_' || c.is_digit(radix))
+    {
+        return None;
+    }
+    Some(match text.contains('_') {
+        true => Cow::Owned(text.replace('_', "")),
+        false => Cow::Borrowed(text),
+    })
+}
+
+macro_rules! integer_literal {
+    ($($ty:ty),*) => {
+        $(
+            /// Decimal, or hexadecimal, octal or binary after `0x`, `0o` or
+            /// `0b`, with `_`s between digits
+            impl Literal for $ty {
+                fn parse_literal(text: &str) -> Option<Self> {
+                    if let Ok(v) = text.parse() {
+                        return Some(v);
+                    }
+                    let (sign, text) = match text.strip_prefix('-') {
+                        Some(text) => ("-", text),
+                        None => ("", text.strip_prefix('+').unwrap_or(text)),
+                    };
+                    let (radix, text) = match text.get(..2) {
+                        Some("0x" | "0X") => (16, &text[2..]),
+                        Some("0o" | "0O") => (8, &text[2..]),
+                        Some("0b" | "0B") => (2, &text[2..]),
+                        _ => (10, text),
+                    };
+                    // Rust allows a `_` straight after the prefix
+                    let text = match radix {
+                        10 => text,
+                        _ => text.trim_start_matches('_'),
+                    };
+                    let digits = digits(text, radix)?;
+                    match sign {
+                        "" => <$ty>::from_str_radix(&digits, radix).ok(),
+                        sign => <$ty>::from_str_radix(&format!("{sign}{digits}"), radix).ok(),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+integer_literal!(i8, i16, i32, i64, i128, u8, u16, u32, u64, u128);
+
+macro_rules! float_literal {
+    ($($ty:ty),*) => {
+        $(
+            /// `inf`, `-inf` and `nan` are the only ways to write a float that
+            /// isn't finite
+            impl Literal for $ty {
+                fn parse_literal(text: &str) -> Option<Self> {
+                    match text {
+                        "inf" | "+inf" => return Some(<$ty>::INFINITY),
+                        "-inf" => return Some(<$ty>::NEG_INFINITY),
+                        "nan" => return Some(<$ty>::NAN),
+                        _ => {}
+                    }
+                    if !text.contains('_') {
+                        return text.parse().ok().filter(|v: &$ty| v.is_finite());
+                    }
+                    // Every `_` must sit between two digits
+                    let bytes = text.as_bytes();
+                    let separates_digits = |(i, b): (usize, &u8)| {
+                        *b != b'_'
+                            || (i > 0
+                                && bytes[i - 1].is_ascii_digit()
+                                && bytes.get(i + 1).is_some_and(u8::is_ascii_digit))
+                    };
+                    if !bytes.iter().enumerate().all(separates_digits) {
+                        return None;
+                    }
+                    text.replace('_', "").parse().ok().filter(|v: &$ty| v.is_finite())
+                }
+
+                fn is_finite(&self) -> bool {
+                    <$ty>::is_finite(*self)
+                }
+            }
+        )*
+    };
+}
+
+float_literal!(f32, f64);
+
+impl Literal for char {
+    fn parse_literal(text: &str) -> Option<Self> {
+        text.parse().ok()
+    }
+}
+
+impl TsonDeserializer<'_> {
+    /// Parses a number or char, leaving infinities and NaN to the options
+    fn literal<T: Literal>(&self, text: &str, expected: &str) -> Result<T, Error> {
+        Literal::parse_literal(text)
+            .filter(|v: &T| v.is_finite() || self.options.non_finite_floats)
+            .ok_or_else(|| {
+                self.error(ErrorKind::InvalidValue(
+                    text.to_owned(),
+                    expected.to_owned(),
+                ))
+            })
+    }
+}
+
 macro_rules! deserialize_value {
     ($fn_name:ident, $visitor:ident, $expected:expr) => {
         fn $fn_name<V>(self, visitor: V) -> Result<V::Value, Self::Error>
         where
             V: Visitor<'de>,
         {
-            let text = self.text()?;
-            self.that_shit()?;
-            visitor.$visitor(
-                text.parse()
-                    .map_err(|_| Error::InvalidValue(text.to_string(), String::from($expected)))?,
-            )
+            let text = self.text_run()?;
+            visitor.$visitor(self.literal(text, $expected)?)
         }
     };
 }
@@ -225,21 +1155,92 @@ macro_rules! deserialize_value {
 impl<'de> Deserializer<'de> for &mut TsonDeserializer<'de> {
     type Error = Error;
 
-    fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        if self.depth == 0 {
+            self.positional = false;
+            return self.deserialize_block(visitor);
+        }
+        if self.positional && self.open_nested_seq() {
+            self.positional = false;
+            return self.deserialize_seq(visitor);
+        }
+
+        let prefix = self.prefix()?;
+        let positional = std::mem::take(&mut self.positional);
+        let first_map = std::mem::take(&mut self.first_map);
+        match prefix {
+            Some(Token::Dont) => {
+                self.that_shit()?;
+                return visitor.visit_bool(false);
+            }
+            Some(Token::InTheory) => {
+                self.that_shit()?;
+                return visitor.visit_none();
+            }
+            _ => {}
+        }
+
+        let gap = self.gap();
+        match self.peek()? {
+            None => Err(self.eof()),
+            // A keyed value made of nothing but intensifiers is text, unless
+            // they sit on a line of their own, where they are a `true` opening
+            // a sequence
+            Some(Token::ThatShit)
+                if !positional && leading_intensifiers(gap, &self.dialect.fuckin) != gap.len() =>
+            {
+                if gap.contains('\n') {
+                    self.deserialize_block(visitor)
+                } else {
+                    self.deserialize_scalar(visitor)
+                }
+            }
+            Some(Token::ThatShit) => {
+                self.next()?;
+                visitor.visit_bool(true)
+            }
+            Some(Token::Text(_) | Token::Raw(_) | Token::Dont | Token::InTheory)
+                if !positional && gap.contains('\n') =>
+            {
+                self.deserialize_block(visitor)
+            }
+            Some(Token::Text(_) | Token::Raw(_)) => {
+                match self.peek_second() {
+                    Some((Token::ThatShit, _)) | None => self.deserialize_scalar(visitor),
+                    Some((_, true)) => {
+                        let variant = self.text()?;
+                        visitor.visit_map(TsonVariantBlockAccess {
+                            deserializer: self,
+                            variant: Some(variant),
+                        })
+                    }
+                    // Maps nested in a sequence are set apart by a blank line
+                    Some((_, false)) if positional && (first_map || has_blank_line(gap)) => {
+                        self.deserialize_map(visitor)
+                    }
+                    Some((_, false)) => self.deserialize_scalar(visitor),
+                }
+            }
+            Some(token) => Err(self.error(ErrorKind::InvalidValue(
+                token.in_dialect(&self.dialect),
+                String::from("a value"),
+            ))),
+        }
     }
 
     deserialize_value!(deserialize_i8, visit_i8, "i8");
     deserialize_value!(deserialize_i16, visit_i16, "i16");
     deserialize_value!(deserialize_i32, visit_i32, "i32");
     deserialize_value!(deserialize_i64, visit_i64, "i64");
+    deserialize_value!(deserialize_i128, visit_i128, "i128");
     deserialize_value!(deserialize_u8, visit_u8, "u8");
     deserialize_value!(deserialize_u16, visit_u16, "u16");
     deserialize_value!(deserialize_u32, visit_u32, "u32");
     deserialize_value!(deserialize_u64, visit_u64, "u64");
+    deserialize_value!(deserialize_u128, visit_u128, "u128");
     deserialize_value!(deserialize_f32, visit_f32, "f32");
     deserialize_value!(deserialize_f64, visit_f64, "f64");
     deserialize_value!(deserialize_char, visit_char, "char");
@@ -248,12 +1249,7 @@ impl<'de> Deserializer<'de> for &mut TsonDeserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        let result = if self.prefix_token == Some(Token::Dont) {
-            self.prefix_token = None;
-            false
-        } else {
-            true
-        };
+        let result = self.prefix()? != Some(Token::Dont);
 
         self.that_shit()?;
         visitor.visit_bool(result)
@@ -263,28 +1259,8 @@ impl<'de> Deserializer<'de> for &mut TsonDeserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        let start_text = self.text()?;
-
-        let start_ptr = start_text.as_ptr();
-        let mut end_ptr = unsafe { start_text.as_ptr().offset(start_text.len() as isize) };
-
-        loop {
-            let next_token = self.next()?;
-            match next_token {
-                Token::Text(text) => {
-                    end_ptr = unsafe { text.as_ptr().offset(text.len() as isize) };
-                }
-                Token::ThatShit => break,
-                _ => {}
-            }
-        }
-
-        visitor.visit_borrowed_str(unsafe {
-            std::str::from_utf8_unchecked(std::slice::from_raw_parts(
-                start_ptr,
-                end_ptr as usize - start_ptr as usize,
-            ))
-        })
+        let text = self.text_run()?;
+        visitor.visit_borrowed_str(text)
     }
 
     fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Self::Error>
@@ -298,13 +1274,27 @@ impl<'de> Deserializer<'de> for &mut TsonDeserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        self.deserialize_seq(visitor)
+        self.deserialize_byte_buf(visitor)
     }
 
+    /// Reads a `base64:...` block, or a sequence of one byte per line as bytes
+    /// used to be written
     fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
+        if let Some(Token::Text(text)) = self.peek()?
+            && text.starts_with(base64::PREFIX)
+        {
+            let text = self.text_run()?;
+            let bytes = base64::decode(text).ok_or_else(|| {
+                self.error(ErrorKind::InvalidValue(
+                    text.to_owned(),
+                    String::from("base64"),
+                ))
+            })?;
+            return visitor.visit_byte_buf(bytes);
+        }
         self.deserialize_seq(visitor)
     }
 
@@ -312,12 +1302,15 @@ impl<'de> Deserializer<'de> for &mut TsonDeserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        if self.prefix_token == Some(Token::InTheory) {
-            self.prefix_token = None;
-            self.that_shit()?;
-            visitor.visit_none()
-        } else {
-            visitor.visit_some(self)
+        match self.prefix()? {
+            Some(Token::InTheory) => {
+                self.that_shit()?;
+                visitor.visit_none()
+            }
+            prefix => {
+                self.prefix_token = prefix;
+                visitor.visit_some(self)
+            }
         }
     }
 
@@ -327,10 +1320,10 @@ impl<'de> Deserializer<'de> for &mut TsonDeserializer<'de> {
     {
         let next_token = self.next()?;
         if next_token != Token::Text("unit") {
-            return Err(Error::InvalidType(
-                next_token.to_string(),
+            return Err(self.error(ErrorKind::InvalidType(
+                next_token.in_dialect(&self.dialect),
                 String::from("unit"),
-            ));
+            )));
         };
         self.that_shit()?;
         visitor.visit_unit()
@@ -346,10 +1339,10 @@ impl<'de> Deserializer<'de> for &mut TsonDeserializer<'de> {
     {
         let next_token = self.next()?;
         if next_token != Token::Text(name) {
-            return Err(Error::InvalidType(
-                next_token.to_string(),
+            return Err(self.error(ErrorKind::InvalidType(
+                next_token.in_dialect(&self.dialect),
                 String::from(name),
-            ));
+            )));
         };
         self.that_shit()?;
         visitor.visit_unit()
@@ -370,9 +1363,27 @@ impl<'de> Deserializer<'de> for &mut TsonDeserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        let result = visitor.visit_seq(TsonSeqAccess { deserializer: self })?;
+        // Only a sequence that is itself an element is opened by a `fuckin` line
+        let positional = std::mem::take(&mut self.positional);
+        let nested = positional && self.open_nested_seq();
+        // Anywhere but the top of the document, scalars may sit on the line of
+        // the sequence's key as `[a,b]`
+        if self.depth != 0
+            && !nested
+            && (positional || !self.gap().contains('\n'))
+            && let Some(Token::Text(text)) = self.peek()?
+            && key::is_list(text)
+        {
+            let text = self.text_run()?;
+            return self.inline_seq(text, visitor);
+        }
+        self.depth += 1;
+        let result = visitor
+            .visit_seq(TsonSeqAccess { deserializer: self })
+            .map_err(|err| self.locate(err))?;
+        self.depth -= 1;
 
-        self.oh_yeah_or_none()?;
+        self.close_block()?;
 
         Ok(result)
     }
@@ -400,9 +1411,13 @@ impl<'de> Deserializer<'de> for &mut TsonDeserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        let result = visitor.visit_map(TsonMapAccess { deserializer: self })?;
+        self.depth += 1;
+        let result = visitor
+            .visit_map(TsonMapAccess { deserializer: self })
+            .map_err(|err| self.locate(err))?;
+        self.depth -= 1;
 
-        self.oh_yeah_or_none()?;
+        self.close_block()?;
 
         Ok(result)
     }
@@ -419,6 +1434,9 @@ impl<'de> Deserializer<'de> for &mut TsonDeserializer<'de> {
         self.deserialize_map(visitor)
     }
 
+    /// Besides `key Variant` on one line, accepts the variant on its own line
+    /// inside a block of its own (`key`, `Variant`, body, `oh yeah`, `oh yeah`),
+    /// which is how a single-entry map such as a [`crate::Value`] is written
     fn deserialize_enum<V>(
         self,
         _name: &'static str,
@@ -428,22 +1446,38 @@ impl<'de> Deserializer<'de> for &mut TsonDeserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        visitor.visit_enum(TsonEnumAccess { deserializer: self })
+        let positional = std::mem::take(&mut self.positional);
+        let block = !positional && self.depth != 0 && self.gap().contains('\n');
+
+        // A variant body is a block of its own, even at the top of the document
+        self.depth += 1;
+        let result = visitor.visit_enum(TsonEnumAccess { deserializer: self })?;
+        self.depth -= 1;
+
+        if block {
+            self.oh_yeah_or_none()?;
+        }
+        Ok(result)
     }
 
+    /// Keys and variant names are read by [`MapAccessDeserializer`], so this is
+    /// only reached for a value, such as the tag of an internally tagged enum,
+    /// which is taken up to its `that shit` like any other string
     fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        let text = self.identifier()?;
-        visitor.visit_borrowed_str(text)
+        self.deserialize_str(visitor)
     }
 
-    fn deserialize_ignored_any<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    /// Skips a value by reading it as [`Deserializer::deserialize_any`] would,
+    /// so a scalar is taken up to its `that shit` and a block (including an enum
+    /// variant block) up to its matching `oh yeah`
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        self.deserialize_any(visitor)
     }
 }
 
@@ -451,6 +1485,20 @@ struct MapAccessDeserializer<'de, 'a> {
     deserializer: &'a mut TsonDeserializer<'de>,
 }
 
+impl<'de> MapAccessDeserializer<'de, '_> {
+    /// Reads a key written as `(a,b)` or `Variant(a,b)`, or a unit variant
+    fn composite<T>(
+        &mut self,
+        read: impl FnOnce(&mut KeyDeserializer<'de>) -> Result<T, Error>,
+    ) -> Result<T, Error> {
+        let text = self.deserializer.identifier()?;
+        let mut key = KeyDeserializer::new(text, self.deserializer.options.non_finite_floats);
+        read(&mut key)
+            .and_then(|value| key.end().map(|()| value))
+            .map_err(|err| self.deserializer.locate(err))
+    }
+}
+
 macro_rules! deserialize_key_value {
     ($fn_name:ident, $visitor:ident, $expected:expr) => {
         fn $fn_name<V>(self, visitor: V) -> Result<V::Value, Self::Error>
@@ -458,10 +1506,7 @@ macro_rules! deserialize_key_value {
             V: Visitor<'de>,
         {
             let text = self.deserializer.identifier()?;
-            visitor.$visitor(
-                text.parse()
-                    .map_err(|_| Error::InvalidValue(text.to_string(), String::from($expected)))?,
-            )
+            visitor.$visitor(self.deserializer.literal(text, $expected)?)
         }
     };
 }
@@ -469,21 +1514,23 @@ macro_rules! deserialize_key_value {
 impl<'de, 'a> Deserializer<'de> for &mut MapAccessDeserializer<'de, 'a> {
     type Error = Error;
 
-    fn deserialize_any<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        self.deserialize_identifier(visitor)
     }
 
     deserialize_key_value!(deserialize_i8, visit_i8, "i8");
     deserialize_key_value!(deserialize_i16, visit_i16, "i16");
     deserialize_key_value!(deserialize_i32, visit_i32, "i32");
     deserialize_key_value!(deserialize_i64, visit_i64, "i64");
+    deserialize_key_value!(deserialize_i128, visit_i128, "i128");
     deserialize_key_value!(deserialize_u8, visit_u8, "u8");
     deserialize_key_value!(deserialize_u16, visit_u16, "u16");
     deserialize_key_value!(deserialize_u32, visit_u32, "u32");
     deserialize_key_value!(deserialize_u64, visit_u64, "u64");
+    deserialize_key_value!(deserialize_u128, visit_u128, "u128");
     deserialize_key_value!(deserialize_f32, visit_f32, "f32");
     deserialize_key_value!(deserialize_f64, visit_f64, "f64");
     deserialize_key_value!(deserialize_char, visit_char, "char");
@@ -498,10 +1545,10 @@ impl<'de, 'a> Deserializer<'de> for &mut MapAccessDeserializer<'de, 'a> {
         } else if text == "false" {
             visitor.visit_bool(false)
         } else {
-            Err(Error::InvalidValue(
+            Err(self.deserializer.error(ErrorKind::InvalidValue(
                 text.to_owned(),
                 String::from("true, false"),
-            ))
+            )))
         }
     }
 
@@ -524,21 +1571,22 @@ impl<'de, 'a> Deserializer<'de> for &mut MapAccessDeserializer<'de, 'a> {
     where
         V: Visitor<'de>,
     {
-        Err(Error::KeyMustBeStringable)
+        Err(self.deserializer.error(ErrorKind::KeyMustBeStringable))
     }
 
     fn deserialize_byte_buf<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        Err(Error::KeyMustBeStringable)
+        Err(self.deserializer.error(ErrorKind::KeyMustBeStringable))
     }
 
     fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        if self.deserializer.reader.peek() == Some(&Token::InTheory) {
+        if self.deserializer.peek()? == Some(Token::InTheory) {
+            self.deserializer.next()?;
             visitor.visit_none()
         } else {
             visitor.visit_some(self)
@@ -551,10 +1599,10 @@ impl<'de, 'a> Deserializer<'de> for &mut MapAccessDeserializer<'de, 'a> {
     {
         let next_token = self.deserializer.identifier()?;
         if next_token != "unit" {
-            return Err(Error::InvalidType(
+            return Err(self.deserializer.error(ErrorKind::InvalidType(
                 next_token.to_string(),
                 String::from("unit"),
-            ));
+            )));
         };
         visitor.visit_unit()
     }
@@ -569,10 +1617,10 @@ impl<'de, 'a> Deserializer<'de> for &mut MapAccessDeserializer<'de, 'a> {
     {
         let next_token = self.deserializer.identifier()?;
         if next_token != name {
-            return Err(Error::InvalidType(
+            return Err(self.deserializer.error(ErrorKind::InvalidType(
                 next_token.to_string(),
                 String::from(name),
-            ));
+            )));
         };
         visitor.visit_unit()
     }
@@ -588,37 +1636,37 @@ impl<'de, 'a> Deserializer<'de> for &mut MapAccessDeserializer<'de, 'a> {
         visitor.visit_newtype_struct(self)
     }
 
-    fn deserialize_seq<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        Err(Error::KeyMustBeStringable)
+        self.composite(|key| key.deserialize_seq(visitor))
     }
 
-    fn deserialize_tuple<V>(self, _len: usize, _visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_tuple<V>(self, len: usize, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        Err(Error::KeyMustBeStringable)
+        self.composite(|key| key.deserialize_tuple(len, visitor))
     }
 
     fn deserialize_tuple_struct<V>(
         self,
-        _name: &'static str,
-        _len: usize,
-        _visitor: V,
+        name: &'static str,
+        len: usize,
+        visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        Err(Error::KeyMustBeStringable)
+        self.composite(|key| key.deserialize_tuple_struct(name, len, visitor))
     }
 
     fn deserialize_map<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        Err(Error::KeyMustBeStringable)
+        Err(self.deserializer.error(ErrorKind::KeyMustBeStringable))
     }
 
     fn deserialize_struct<V>(
@@ -630,33 +1678,34 @@ impl<'de, 'a> Deserializer<'de> for &mut MapAccessDeserializer<'de, 'a> {
     where
         V: Visitor<'de>,
     {
-        Err(Error::KeyMustBeStringable)
+        Err(self.deserializer.error(ErrorKind::KeyMustBeStringable))
     }
 
     fn deserialize_enum<V>(
         self,
-        _name: &'static str,
-        _variants: &'static [&'static str],
-        _visitor: V,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
     ) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        Err(Error::KeyMustBeStringable)
+        self.composite(|key| key.deserialize_enum(name, variants, visitor))
     }
 
     fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        self.deserializer.deserialize_identifier(visitor)
+        let text = self.deserializer.identifier()?;
+        visitor.visit_borrowed_str(text)
     }
 
-    fn deserialize_ignored_any<V>(self, _visitor: V) -> Result<V::Value, Self::Error>
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Self::Error>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        self.deserialize_identifier(visitor)
     }
 }
 
@@ -671,8 +1720,8 @@ impl<'de, 'a> MapAccess<'de> for TsonMapAccess<'de, 'a> {
     where
         K: de::DeserializeSeed<'de>,
     {
-        let next_token = self.deserializer.reader.peek();
-        if next_token.is_none() || next_token == Some(&Token::OhYeah) {
+        let next_token = self.deserializer.peek()?;
+        if next_token.is_none() || next_token == Some(Token::OhYeah) {
             Ok(None)
         } else {
             seed.deserialize(&mut MapAccessDeserializer {
@@ -686,6 +1735,36 @@ impl<'de, 'a> MapAccess<'de> for TsonMapAccess<'de, 'a> {
     where
         V: de::DeserializeSeed<'de>,
     {
+        self.deserializer.positional = false;
+        seed.deserialize(&mut *self.deserializer)
+    }
+}
+
+/// Presents an enum variant block, as in `key Variant` followed by its body,
+/// as a map with a single entry for [`Deserializer::deserialize_any`]
+struct TsonVariantBlockAccess<'de, 'a> {
+    deserializer: &'a mut TsonDeserializer<'de>,
+    variant: Option<&'de str>,
+}
+
+impl<'de, 'a> MapAccess<'de> for TsonVariantBlockAccess<'de, 'a> {
+    type Error = Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, Self::Error>
+    where
+        K: de::DeserializeSeed<'de>,
+    {
+        self.variant
+            .take()
+            .map(|variant| seed.deserialize(BorrowedStrDeserializer::new(variant)))
+            .transpose()
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, Self::Error>
+    where
+        V: de::DeserializeSeed<'de>,
+    {
+        self.deserializer.positional = false;
         seed.deserialize(&mut *self.deserializer)
     }
 }
@@ -702,7 +1781,9 @@ impl<'de, 'a> EnumAccess<'de> for TsonEnumAccess<'de, 'a> {
     where
         V: de::DeserializeSeed<'de>,
     {
-        let value = seed.deserialize(&mut *self.deserializer)?;
+        let value = seed.deserialize(&mut MapAccessDeserializer {
+            deserializer: &mut *self.deserializer,
+        })?;
 
         Ok((value, self))
     }
@@ -719,7 +1800,10 @@ impl<'de, 'a> VariantAccess<'de> for TsonEnumAccess<'de, 'a> {
     where
         T: de::DeserializeSeed<'de>,
     {
-        seed.deserialize(&mut *self.deserializer)
+        self.deserializer.positional = true;
+        let value = seed.deserialize(&mut *self.deserializer)?;
+        self.deserializer.oh_yeah_or_none()?;
+        Ok(value)
     }
 
     fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, Self::Error>
@@ -752,11 +1836,21 @@ impl<'de, 'a> SeqAccess<'de> for TsonSeqAccess<'de, 'a> {
     where
         T: de::DeserializeSeed<'de>,
     {
-        let next_token = self.deserializer.reader.peek();
-        if next_token.is_none() || next_token == Some(&Token::OhYeah) {
-            Ok(None)
-        } else {
-            seed.deserialize(&mut *self.deserializer).map(Some)
+        match self.deserializer.peek()? {
+            None => Ok(None),
+            // An empty element is set apart by a lone `fuckin` line
+            Some(Token::OhYeah)
+                if !opens_nested_seq(
+                    self.deserializer.gap(),
+                    &self.deserializer.dialect.fuckin,
+                ) =>
+            {
+                Ok(None)
+            }
+            Some(_) => {
+                self.deserializer.positional = true;
+                seed.deserialize(&mut *self.deserializer).map(Some)
+            }
         }
     }
 }
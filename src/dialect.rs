@@ -0,0 +1,506 @@
+use std::{borrow::Cow, ops::Range, rc::Rc};
+
+use serde_core::{Deserialize, de::IgnoredAny};
+
+use crate::{
+    SerializerOptions, Value,
+    de::{self, Error, ErrorKind, Location, Token, TsonDeserializer, TsonLexer, is_space},
+};
+
+/// The words a document is written with. Every dialect reads and writes the
+/// same documents, only with different keywords, so a document can be
+/// [translated](translate) from one to another without losing anything.
+///
+/// A document names its dialect on a `#!tson` first line, either as
+/// `#!tson friendly` or by listing the keywords that differ from TSON's:
+///
+/// ```text
+/// #!tson that_shit="that stuff" fuckin=really
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dialect {
+    pub that_shit: Cow<'static, str>,
+    pub oh_yeah: Cow<'static, str>,
+    pub dont: Cow<'static, str>,
+    pub in_theory: Cow<'static, str>,
+    /// The intensifier, skipped wherever it is followed by whitespace
+    pub fuckin: Cow<'static, str>,
+    /// Opens a comment running to the end of the line
+    pub psst: Cow<'static, str>,
+    /// Opens a comment running up to [`go_on`](Self::go_on)
+    pub shut_the_fuck_up: Cow<'static, str>,
+    pub go_on: Cow<'static, str>,
+}
+
+pub(crate) static TSON: Dialect = Dialect::TSON;
+
+const HEADER: &str = "#!tson";
+
+impl Dialect {
+    pub const TSON: Dialect = Dialect {
+        that_shit: Cow::Borrowed("that shit"),
+        oh_yeah: Cow::Borrowed("oh yeah"),
+        dont: Cow::Borrowed("dont"),
+        in_theory: Cow::Borrowed("in theory"),
+        fuckin: Cow::Borrowed("fuckin"),
+        psst: Cow::Borrowed("psst"),
+        shut_the_fuck_up: Cow::Borrowed("shut the fuck up"),
+        go_on: Cow::Borrowed("go on"),
+    };
+
+    /// Safe for work
+    pub const FRIENDLY: Dialect = Dialect {
+        that_shit: Cow::Borrowed("that stuff"),
+        oh_yeah: Cow::Borrowed("oh yeah"),
+        dont: Cow::Borrowed("dont"),
+        in_theory: Cow::Borrowed("in theory"),
+        fuckin: Cow::Borrowed("really"),
+        psst: Cow::Borrowed("psst"),
+        shut_the_fuck_up: Cow::Borrowed("hush"),
+        go_on: Cow::Borrowed("go on"),
+    };
+
+    fn keywords(&self) -> [(&'static str, &str); 8] {
+        [
+            ("that_shit", &self.that_shit),
+            ("oh_yeah", &self.oh_yeah),
+            ("dont", &self.dont),
+            ("in_theory", &self.in_theory),
+            ("fuckin", &self.fuckin),
+            ("psst", &self.psst),
+            ("shut_the_fuck_up", &self.shut_the_fuck_up),
+            ("go_on", &self.go_on),
+        ]
+    }
+
+    fn keyword_mut(&mut self, name: &str) -> Option<&mut Cow<'static, str>> {
+        Some(match name {
+            "that_shit" => &mut self.that_shit,
+            "oh_yeah" => &mut self.oh_yeah,
+            "dont" => &mut self.dont,
+            "in_theory" => &mut self.in_theory,
+            "fuckin" => &mut self.fuckin,
+            "psst" => &mut self.psst,
+            "shut_the_fuck_up" => &mut self.shut_the_fuck_up,
+            "go_on" => &mut self.go_on,
+            _ => return None,
+        })
+    }
+
+    /// Checks that every keyword can be told apart from the others and from
+    /// text
+    pub fn validate(&self) -> Result<(), Error> {
+        let invalid = |message: String| Err(ErrorKind::InvalidDialect(message).into());
+        let keywords = self.keywords();
+        for (i, (name, keyword)) in keywords.iter().enumerate() {
+            let words: Vec<_> = keyword.split_whitespace().collect();
+            if words.is_empty() {
+                return invalid(format!("`{name}` is empty"));
+            }
+            if words.len() > 1 && matches!(*name, "dont" | "fuckin" | "psst") {
+                return invalid(format!("`{name}` must be a single word"));
+            }
+            if let Some(word) = words
+                .iter()
+                .find(|word| word.starts_with('#') || word.contains('"'))
+            {
+                return invalid(format!("`{name}` contains `{word}`"));
+            }
+            if words.join(" ") != **keyword {
+                return invalid(format!("`{name}` must be words separated by single spaces"));
+            }
+            for (other_name, other) in &keywords[i + 1..] {
+                if keyword == other {
+                    return invalid(format!("`{name}` and `{other_name}` are both `{keyword}`"));
+                }
+            }
+            if *name != "fuckin" && words.contains(&&*self.fuckin) {
+                return invalid(format!("`{name}` contains the intensifier"));
+            }
+            if !matches!(*name, "dont" | "psst") && (words[0] == self.dont || words[0] == self.psst)
+            {
+                return invalid(format!("`{name}` starts with a keyword of its own"));
+            }
+        }
+        Ok(())
+    }
+
+    /// The `#!tson` line naming this dialect, empty for TSON itself
+    pub fn header(&self) -> String {
+        if *self == Dialect::TSON {
+            return String::new();
+        }
+        if *self == Dialect::FRIENDLY {
+            return format!("{HEADER} friendly\n");
+        }
+
+        let mut header = String::from(HEADER);
+        for ((name, keyword), (_, tson)) in self.keywords().into_iter().zip(TSON.keywords()) {
+            if keyword != tson {
+                header.push_str(&format!(" {name}=\"{keyword}\""));
+            }
+        }
+        header.push('\n');
+        header
+    }
+
+    /// Reads the dialect named by a `#!tson` first line, along with where the
+    /// document after it starts. Documents without one are TSON
+    pub fn detect(source: &str) -> Result<(Dialect, usize), Error> {
+        let Some(rest) = source.strip_prefix(HEADER) else {
+            return Ok((Dialect::TSON, 0));
+        };
+        let line = rest.split('\n').next().unwrap_or_default();
+        let end = (HEADER.len() + line.len() + 1).min(source.len());
+        if !line.is_empty() && !line.starts_with(char::is_whitespace) {
+            return Ok((Dialect::TSON, 0));
+        }
+        let locate = |kind: ErrorKind| Error::new(kind, Location::new(source, 0..end));
+
+        let dialect = match line.trim() {
+            "" | "tson" => Dialect::TSON,
+            "friendly" => Dialect::FRIENDLY,
+            mut pairs => {
+                let mut dialect = Dialect::TSON;
+                while !pairs.is_empty() {
+                    let Some((name, value)) = pairs.split_once('=') else {
+                        return Err(locate(ErrorKind::InvalidDialect(format!(
+                            "expected `keyword=value` in `{pairs}`"
+                        ))));
+                    };
+                    let (value, rest) = match value.strip_prefix('"') {
+                        Some(quoted) => quoted.split_once('"').unwrap_or((quoted, "")),
+                        None => value.split_once(char::is_whitespace).unwrap_or((value, "")),
+                    };
+                    let Some(keyword) = dialect.keyword_mut(name.trim()) else {
+                        return Err(locate(ErrorKind::InvalidDialect(format!(
+                            "unknown keyword `{}`",
+                            name.trim()
+                        ))));
+                    };
+                    *keyword = Cow::Owned(value.split_whitespace().collect::<Vec<_>>().join(" "));
+                    pairs = rest.trim_start();
+                }
+                dialect
+            }
+        };
+        dialect.validate().map_err(|err| locate(err.into_kind()))?;
+        Ok((dialect, end))
+    }
+}
+
+impl Default for Dialect {
+    fn default() -> Self {
+        Dialect::TSON
+    }
+}
+
+/// Something [`DialectLexer`] steps over or takes
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) enum Lexeme<'source> {
+    Token(Result<Token<'source>, ()>),
+    Intensifier,
+    LineComment,
+    /// Holds where the `shut the fuck up` opening it ends
+    BlockComment(usize),
+}
+
+/// Lexes any dialect into the same tokens `Token::lexer` makes of TSON
+#[derive(Clone)]
+pub(crate) struct DialectLexer<'source> {
+    source: &'source str,
+    position: usize,
+    dialect: Rc<Dialect>,
+}
+
+impl<'source> DialectLexer<'source> {
+    pub(crate) fn new(source: &'source str, start: usize, dialect: Rc<Dialect>) -> Self {
+        Self {
+            source,
+            position: start,
+            dialect,
+        }
+    }
+
+    fn word(&self, at: usize) -> &'source str {
+        let rest = &self.source[at..];
+        &rest[..rest.find(is_space).unwrap_or(rest.len())]
+    }
+
+    fn skip_space(&self, at: usize) -> usize {
+        let rest = &self.source[at..];
+        at + rest.find(|c| !is_space(c)).unwrap_or(rest.len())
+    }
+
+    /// Matches a keyword of one or more words starting at `at`, returning
+    /// where it ends. Like `Token::lexer`, the last word of a longer keyword
+    /// only has to start the word it is matched against
+    fn keyword(&self, at: usize, keyword: &str, intensifiable: bool) -> Option<usize> {
+        let mut words = keyword.split(' ').peekable();
+        let mut position = at;
+        while let Some(expected) = words.next() {
+            if words.peek().is_none() && position != at {
+                return self.source[position..]
+                    .starts_with(expected)
+                    .then_some(position + expected.len());
+            }
+            if self.word(position) != expected {
+                return None;
+            }
+            let end = position + expected.len();
+            if words.peek().is_none() {
+                return Some(end);
+            }
+            position = self.skip_space(end);
+            if position == end {
+                return None;
+            }
+            while intensifiable && self.intensifier(position).is_some() {
+                position = self.skip_space(position + self.dialect.fuckin.len());
+            }
+        }
+        None
+    }
+
+    fn intensifier(&self, at: usize) -> Option<usize> {
+        let end = at + self.dialect.fuckin.len();
+        (self.word(at) == self.dialect.fuckin && end < self.source.len()).then_some(end)
+    }
+
+    /// Finds the end of the `go on` closing a block comment whose opening ends
+    /// at `at`
+    fn comment_end(&self, at: usize) -> Option<usize> {
+        let first = self.dialect.go_on.split(' ').next().unwrap_or_default();
+        let rest = &self.source[at..];
+        for (index, _) in rest.match_indices(first) {
+            let start = at + index;
+            if !rest[..index].ends_with(is_space) {
+                continue;
+            }
+            if let Some(end) = self.keyword(start, &self.dialect.go_on, false)
+                && self.source[end..].chars().next().is_none_or(is_space)
+            {
+                return Some(end);
+            }
+        }
+        None
+    }
+
+    fn raw(&self, at: usize) -> Option<(Result<Token<'source>, ()>, usize)> {
+        let rest = &self.source[at..];
+        let hashes = rest.bytes().take_while(|b| *b == b'#').count();
+        if hashes == 0 || rest.as_bytes().get(hashes) != Some(&b'"') {
+            return None;
+        }
+        let opened = at + hashes + 1;
+        let closing = format!("\"{}", "#".repeat(hashes));
+        Some(match self.source[opened..].find(&closing) {
+            Some(index) => (
+                Ok(Token::Raw(&self.source[opened..opened + index])),
+                opened + index + closing.len(),
+            ),
+            None => (Err(()), opened),
+        })
+    }
+
+    /// The next token, intensifier or comment, along with its span
+    pub(crate) fn lexeme(&mut self) -> Option<(Lexeme<'source>, Range<usize>)> {
+        let start = self.skip_space(self.position);
+        if start == self.source.len() {
+            self.position = start;
+            return None;
+        }
+        let word = self.word(start);
+        let dialect = Rc::clone(&self.dialect);
+
+        let (lexeme, end) = if let Some(end) = self.intensifier(start) {
+            (Lexeme::Intensifier, end)
+        } else if word == dialect.psst {
+            let rest = &self.source[start..];
+            (
+                Lexeme::LineComment,
+                start + rest.find('\n').unwrap_or(rest.len()),
+            )
+        } else if let Some(end) = self.keyword(start, &dialect.shut_the_fuck_up, false) {
+            match self.comment_end(end) {
+                Some(close) => (Lexeme::BlockComment(end), close),
+                None => (Lexeme::Token(Err(())), end),
+            }
+        } else if let Some((token, end)) = self.raw(start) {
+            (Lexeme::Token(token), end)
+        } else if let Some(end) = self.keyword(start, &dialect.that_shit, true) {
+            (Lexeme::Token(Ok(Token::ThatShit)), end)
+        } else if let Some(end) = self.keyword(start, &dialect.oh_yeah, true) {
+            (Lexeme::Token(Ok(Token::OhYeah)), end)
+        } else if let Some(end) = self.keyword(start, &dialect.in_theory, true) {
+            (Lexeme::Token(Ok(Token::InTheory)), end)
+        } else if word == dialect.dont {
+            (Lexeme::Token(Ok(Token::Dont)), start + word.len())
+        } else {
+            (Lexeme::Token(Ok(Token::Text(word))), start + word.len())
+        };
+        self.position = end;
+        Some((lexeme, start..end))
+    }
+}
+
+impl<'source> Iterator for DialectLexer<'source> {
+    type Item = (Result<Token<'source>, ()>, Range<usize>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            if let (Lexeme::Token(token), span) = self.lexeme()? {
+                return Some((token, span));
+            }
+        }
+    }
+}
+
+/// Rewrites a document in another dialect, detecting the one it is written in
+/// from its `#!tson` line. Comments, blank lines and the layout of every value
+/// are kept, and text that would read as one of the new keywords is written as
+/// raw text. Intensifiers between the words of a keyword are dropped
+pub fn translate(source: &str, to: &Dialect) -> Result<String, Error> {
+    let (from, start) = Dialect::detect(source)?;
+    translate_from(&source[start..], &from, to)
+}
+
+/// Like [`translate`], but for a document written in `from` without a
+/// `#!tson` line
+pub fn translate_from(source: &str, from: &Dialect, to: &Dialect) -> Result<String, Error> {
+    to.validate()?;
+    let mut values = traced_values(source, from)?.into_iter().peekable();
+    let to_words = (to != &Dialect::TSON).then_some(to);
+
+    let mut translated = to.header();
+    let mut lexer = DialectLexer::new(source, 0, Rc::new(from.clone()));
+    let mut copied = 0;
+    while let Some((lexeme, span)) = lexer.lexeme() {
+        translated.push_str(&source[copied..span.start]);
+        copied = span.end;
+
+        if let Some(value) = values.next_if(|value| value.start == span.start) {
+            let text = &source[value.clone()];
+            if de::needs_raw(text, to_words) {
+                translated.push_str(&de::raw(text));
+            } else {
+                translated.push_str(text);
+            }
+            while lexer.position < value.end {
+                lexer.lexeme();
+            }
+            copied = value.end;
+            continue;
+        }
+
+        let text = &source[span.clone()];
+        match lexeme {
+            Lexeme::Intensifier => translated.push_str(&to.fuckin),
+            Lexeme::LineComment => {
+                translated.push_str(&to.psst);
+                translated.push_str(&text[from.psst.len()..]);
+            }
+            Lexeme::BlockComment(opening) => {
+                let body = &source[opening..span.end - from.go_on.len()];
+                let probe = format!("{body}{}", to.go_on);
+                let check = DialectLexer::new(&probe, 0, Rc::new(to.clone()));
+                if check.comment_end(0) != Some(probe.len()) {
+                    return Err(Error::new(
+                        ErrorKind::InvalidDialect(format!(
+                            "this comment holds `{}`, which would close it early",
+                            to.go_on
+                        )),
+                        Location::new(source, span),
+                    ));
+                }
+                translated.push_str(&to.shut_the_fuck_up);
+                translated.push_str(body);
+                translated.push_str(&to.go_on);
+            }
+            Lexeme::Token(Ok(Token::ThatShit)) => translated.push_str(&to.that_shit),
+            Lexeme::Token(Ok(Token::OhYeah)) => translated.push_str(&to.oh_yeah),
+            Lexeme::Token(Ok(Token::InTheory)) => translated.push_str(&to.in_theory),
+            Lexeme::Token(Ok(Token::Dont)) => translated.push_str(&to.dont),
+            Lexeme::Token(Ok(Token::Text(word))) if de::needs_raw_identifier(word, to_words) => {
+                translated.push_str(&de::raw(word));
+            }
+            Lexeme::Token(_) => translated.push_str(text),
+        }
+    }
+    translated.push_str(&source[copied..]);
+    Ok(translated)
+}
+
+/// Walks a document once to learn which source every value covers, as that is
+/// taken verbatim and may hold anything
+pub(crate) fn traced_values(source: &str, dialect: &Dialect) -> Result<Vec<Range<usize>>, Error> {
+    let mut deserializer = TsonDeserializer::with_dialect(source, 0, dialect.clone());
+    deserializer.trace_values();
+    IgnoredAny::deserialize(&mut deserializer).map_err(|err| deserializer.locate(err))?;
+    deserializer.end()?;
+    Ok(deserializer.into_traced_values())
+}
+
+/// Rewrites a document in the layout of [`SerializerOptions::canonical`], in
+/// the dialect it is written in, as `tson fmt` does. Returns `None` rather
+/// than lose anything along the way, which is when the document holds
+/// comments or a number that would be written differently, such as `007`
+pub fn format(source: &str) -> Result<Option<String>, Error> {
+    let source = source.strip_prefix('\u{feff}').unwrap_or(source);
+    let (dialect, start) = Dialect::detect(source)?;
+    let value: Value = crate::from_str(source)?;
+    let options = SerializerOptions::canonical()
+        .dialect(dialect.clone())
+        .non_finite_floats(true);
+    let formatted = crate::to_string_with(&value, &options).map_err(|err| {
+        Error::from(ErrorKind::Custom(format!(
+            "the document can't be rewritten: {err}"
+        )))
+    })?;
+
+    let mut lexer = DialectLexer::new(source, start, Rc::new(dialect.clone()));
+    let commented = std::iter::from_fn(|| lexer.lexeme())
+        .any(|(lexeme, _)| matches!(lexeme, Lexeme::LineComment | Lexeme::BlockComment(_)));
+    if commented || !reads_the_same(source, &formatted, &dialect) {
+        return Ok(None);
+    }
+    Ok(Some(formatted))
+}
+
+/// Whether two documents read as the same value, with NaNs equal to each
+/// other, and write every number and `unit` the same way. Strings read the
+/// same whether they are written raw or not, but the text of a number is only
+/// kept when it is read into a string
+fn reads_the_same(source: &str, formatted: &str, dialect: &Dialect) -> bool {
+    let canonical = |source| {
+        let value: Value = crate::from_str(source).ok()?;
+        let options = SerializerOptions::canonical().non_finite_floats(true);
+        crate::to_string_with(&value, &options).ok()
+    };
+    let scalars = |source: &str| -> Option<Vec<_>> {
+        let (_, start) = Dialect::detect(source).ok()?;
+        let body = &source[start..];
+        let mut scalars: Vec<_> = traced_values(body, dialect)
+            .ok()?
+            .into_iter()
+            .map(|value| body[value].to_owned())
+            .filter(|text| de::reads_as_scalar(text))
+            .collect();
+        scalars.sort_unstable();
+        Some(scalars)
+    };
+    canonical(source).is_some()
+        && canonical(source) == canonical(formatted)
+        && scalars(source).is_some()
+        && scalars(source) == scalars(formatted)
+}
+
+impl<'source> TsonLexer<'source> {
+    /// Lexes `source` in a dialect, where `None` stands for TSON
+    pub(crate) fn in_dialect(source: &'source str, dialect: Option<&Dialect>) -> Self {
+        match dialect {
+            Some(dialect) => Self::with_dialect(source, 0, Rc::new(dialect.clone())),
+            None => Self::new(source),
+        }
+    }
+}
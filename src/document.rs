@@ -0,0 +1,410 @@
+use std::{
+    collections::BTreeMap,
+    fmt::{self, Display},
+    ops::Range,
+    str::FromStr,
+};
+
+use serde_core::Serialize;
+use thiserror::Error;
+
+use crate::{
+    Dialect, SerializerOptions, Value,
+    de::{self, Token, TsonDeserializer, TsonLexer},
+    dialect, ser,
+};
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error(transparent)]
+    De(#[from] de::Error),
+    #[error(transparent)]
+    Ser(#[from] ser::Error),
+    #[error("Key path must not be empty")]
+    EmptyPath,
+    #[error("No entry at `{0}`")]
+    NotFound(String),
+    #[error("Entry at `{0}` is not a block of entries")]
+    NotABlock(String),
+    #[error("Entry at `{0}` already exists")]
+    AlreadyExists(String),
+}
+
+/// A TSON document that keeps its source as written, so that editing one entry
+/// leaves every other byte (comments, blank lines, `fuckin`s) untouched. New
+/// entries are written in the dialect named by the document's `#!tson` line
+#[derive(Debug, Clone)]
+pub struct Document {
+    source: String,
+    dialect: Dialect,
+    root: Block,
+}
+
+#[derive(Debug, Clone)]
+struct Block {
+    entries: Vec<Entry>,
+    /// Where the block's closing `oh yeah` starts, or the end of the document
+    end: usize,
+}
+
+#[derive(Debug, Clone)]
+struct Entry {
+    key: String,
+    /// From the start of the entry's first line to the end of its last token
+    span: Range<usize>,
+    key_span: Range<usize>,
+    kind: Kind,
+}
+
+#[derive(Debug, Clone)]
+enum Kind {
+    /// A value ended by `that shit`, holding the span of the value alone
+    Scalar(Range<usize>),
+    /// A bool or none, which live entirely in the prefix before the key
+    Flag,
+    Map(Block),
+    /// Sequences, variant bodies and anything else closed by `oh yeah`
+    Other,
+}
+
+impl Document {
+    pub fn parse(source: impl Into<String>) -> Result<Self, Error> {
+        let source = source.into();
+        let (dialect, _) = Dialect::detect(&source)?;
+        let root = parse(&source)?;
+        Ok(Self {
+            source,
+            dialect,
+            root,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    pub fn into_string(self) -> String {
+        self.source
+    }
+
+    /// Reads the value at a key path, or the whole document for an empty path
+    pub fn get(&self, path: &[&str]) -> Option<Value> {
+        let Some((key, parents)) = path.split_last() else {
+            return crate::from_str(&self.source).ok();
+        };
+        let entry = self.block(parents).ok()?.find(key)?;
+        match crate::from_str_in(&self.source[entry.span.clone()], &self.dialect).ok()? {
+            Value::Map(mut map) => map.remove(&entry.key),
+            _ => None,
+        }
+    }
+
+    pub fn contains(&self, path: &[&str]) -> bool {
+        let Some((key, parents)) = path.split_last() else {
+            return true;
+        };
+        self.block(parents)
+            .is_ok_and(|block| block.find(key).is_some())
+    }
+
+    /// Replaces the value at a key path, adding the entry to the end of its
+    /// block if it is not there yet. A scalar replacing a scalar only rewrites
+    /// the value itself, keeping the key and `that shit` as they were written
+    pub fn set<T: ?Sized + Serialize>(&mut self, path: &[&str], value: &T) -> Result<(), Error> {
+        let (key, parents) = path.split_last().ok_or(Error::EmptyPath)?;
+        let (text, new) = self.render(key, value)?;
+        let block = self.block(parents)?;
+        let (range, replacement) = match block.find(key) {
+            Some(old) => {
+                // The old entry's indentation stays in front of whatever
+                // replaces it
+                let indent = indentation(&self.source, old);
+                let start = old.span.start + indent.len();
+                match (&old.kind, &new.kind) {
+                    (Kind::Scalar(old_value), Kind::Scalar(new_value)) => {
+                        (old_value.clone(), text[new_value.clone()].to_owned())
+                    }
+                    (Kind::Flag, Kind::Flag) => (
+                        start..old.key_span.start,
+                        text[new.span.start..new.key_span.start].to_owned(),
+                    ),
+                    _ => {
+                        let entry = indent_entry(&text, new.span, indent, &self.dialect)?;
+                        (start..old.span.end, entry[indent.len()..].to_owned())
+                    }
+                }
+            }
+            None => {
+                let indent = self.block_indent(parents)?;
+                let entry = indent_entry(&text, new.span, &indent, &self.dialect)?;
+                return self.append(parents, &entry);
+            }
+        };
+        self.splice(range, &replacement)
+    }
+
+    /// Adds an entry to the end of its block, failing if the key is taken
+    pub fn insert<T: ?Sized + Serialize>(&mut self, path: &[&str], value: &T) -> Result<(), Error> {
+        let (key, parents) = path.split_last().ok_or(Error::EmptyPath)?;
+        if self.block(parents)?.find(key).is_some() {
+            return Err(Error::AlreadyExists(path.join(".")));
+        }
+        let (text, new) = self.render(key, value)?;
+        let indent = self.block_indent(parents)?;
+        let entry = indent_entry(&text, new.span, &indent, &self.dialect)?;
+        self.append(parents, &entry)
+    }
+
+    /// Removes an entry along with the rest of its last line, returning
+    /// whether there was one
+    pub fn remove(&mut self, path: &[&str]) -> Result<bool, Error> {
+        let (key, parents) = path.split_last().ok_or(Error::EmptyPath)?;
+        let Some(entry) = self.block(parents)?.find(key) else {
+            return Ok(false);
+        };
+        let mut end = entry.span.end;
+        let rest = &self.source[end..];
+        let line = rest.find('\n').map_or(rest.len(), |newline| newline + 1);
+        if rest[..line].trim().is_empty() {
+            end += line;
+        }
+        let range = entry.span.start..end;
+        self.splice(range, "")?;
+        Ok(true)
+    }
+
+    /// Writes a single entry as the serializer would, nesting its blocks as
+    /// deep as the rest of the document does
+    fn render<T: ?Sized + Serialize>(
+        &self,
+        key: &str,
+        value: &T,
+    ) -> Result<(String, Entry), Error> {
+        let options = SerializerOptions::new()
+            .dialect(self.dialect.clone())
+            .indent(indent_unit(&self.source, &self.root).unwrap_or_default());
+        render(key, value, &options)
+    }
+
+    /// The indentation of the entries of the block at `path`, or for an empty
+    /// block, that of its key one level further in
+    fn block_indent(&self, path: &[&str]) -> Result<String, Error> {
+        if let Some(entry) = self.block(path)?.entries.last() {
+            return Ok(indentation(&self.source, entry).to_owned());
+        }
+        let Some((key, parents)) = path.split_last() else {
+            return Ok(String::new());
+        };
+        let outer = self
+            .block(parents)?
+            .find(key)
+            .map_or("", |entry| indentation(&self.source, entry));
+        let unit = indent_unit(&self.source, &self.root).unwrap_or_default();
+        Ok(format!("{outer}{unit}"))
+    }
+
+    fn block(&self, path: &[&str]) -> Result<&Block, Error> {
+        let mut block = &self.root;
+        for (i, key) in path.iter().enumerate() {
+            let entry = block
+                .find(key)
+                .ok_or_else(|| Error::NotFound(path[..=i].join(".")))?;
+            let Kind::Map(inner) = &entry.kind else {
+                return Err(Error::NotABlock(path[..=i].join(".")));
+            };
+            block = inner;
+        }
+        Ok(block)
+    }
+
+    /// Puts an entry on its own line before the closing `oh yeah` of the block
+    /// at `path`, setting entries spanning several lines off with a blank line
+    fn append(&mut self, path: &[&str], entry: &str) -> Result<(), Error> {
+        let mut at = self.block(path)?.end;
+        // The `oh yeah` keeps its indentation below the new entry
+        let line = self.source[..at]
+            .rfind('\n')
+            .map_or(0, |newline| newline + 1);
+        if self.source[line..at].trim().is_empty() {
+            at = line;
+        }
+        let before = &self.source[..at];
+        let mut text = String::new();
+        if !before.is_empty() && !before.ends_with('\n') {
+            text.push('\n');
+        }
+        if entry.contains('\n') && !before.is_empty() && !before.ends_with("\n\n") {
+            text.push('\n');
+        }
+        text.push_str(entry);
+        text.push('\n');
+        self.splice(at..at, &text)
+    }
+
+    /// Applies an edit, keeping the old source if the result does not parse
+    fn splice(&mut self, range: Range<usize>, text: &str) -> Result<(), Error> {
+        let mut source = self.source.clone();
+        source.replace_range(range, text);
+        self.root = parse(&source)?;
+        self.source = source;
+        Ok(())
+    }
+}
+
+impl Block {
+    /// The last entry with a key, as that is the one a map would keep
+    fn find(&self, key: &str) -> Option<&Entry> {
+        self.entries.iter().rev().find(|entry| entry.key == key)
+    }
+}
+
+impl FromStr for Document {
+    type Err = Error;
+
+    fn from_str(source: &str) -> Result<Self, Self::Err> {
+        Self::parse(source)
+    }
+}
+
+impl Display for Document {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+/// Writes a single entry as the serializer would, returning the text along
+/// with the entry parsed back out of it
+fn render<T: ?Sized + Serialize>(
+    key: &str,
+    value: &T,
+    options: &SerializerOptions,
+) -> Result<(String, Entry), Error> {
+    let text = crate::to_string_with(&BTreeMap::from([(key, value)]), options)?;
+    let entry = parse(&text)?
+        .entries
+        .pop()
+        .ok_or_else(|| Error::NotFound(key.to_owned()))?;
+    Ok((text, entry))
+}
+
+/// Puts `indent` in front of every line of an entry taken from `text`, but for
+/// blank lines and the continuation lines of values, which are read verbatim
+fn indent_entry(
+    text: &str,
+    entry: Range<usize>,
+    indent: &str,
+    dialect: &Dialect,
+) -> Result<String, Error> {
+    let (_, start) = Dialect::detect(text)?;
+    let values = dialect::traced_values(&text[start..], dialect)?;
+    let in_value = |at: usize| {
+        values
+            .iter()
+            .any(|value| start + value.start < at && at < start + value.end)
+    };
+
+    let mut indented = String::new();
+    let mut at = entry.start;
+    for line in text[entry].split_inclusive('\n') {
+        if !line.trim().is_empty() && !in_value(at) {
+            indented.push_str(indent);
+        }
+        indented.push_str(line);
+        at += line.len();
+    }
+    Ok(indented)
+}
+
+/// The whitespace in front of an entry that starts its own line
+fn indentation<'a>(source: &'a str, entry: &Entry) -> &'a str {
+    let start = entry.span.start;
+    if start != 0 && !source[..start].ends_with('\n') {
+        return "";
+    }
+    let line = &source[start..];
+    &line[..line.len() - line.trim_start_matches([' ', '\t']).len()]
+}
+
+/// How much further in than its key the entries of a nested block are, going
+/// by the first such block that is indented at all
+fn indent_unit(source: &str, block: &Block) -> Option<String> {
+    block.entries.iter().find_map(|entry| {
+        let Kind::Map(inner) = &entry.kind else {
+            return None;
+        };
+        let unit = inner
+            .entries
+            .first()
+            .and_then(|first| indentation(source, first).strip_prefix(indentation(source, entry)));
+        match unit {
+            Some(unit) if !unit.is_empty() => Some(unit.to_owned()),
+            _ => indent_unit(source, inner),
+        }
+    })
+}
+
+fn parse(source: &str) -> Result<Block, de::Error> {
+    let (dialect, start) = Dialect::detect(source)?;
+    let mut deserializer = TsonDeserializer::with_dialect(source, start, dialect);
+    deserializer.open_block();
+    let block = parse_block(source, &mut deserializer).map_err(|err| deserializer.locate(err))?;
+    deserializer.end()?;
+    Ok(block)
+}
+
+fn parse_block(source: &str, deserializer: &mut TsonDeserializer) -> Result<Block, de::Error> {
+    let mut entries = Vec::new();
+    let end = loop {
+        let span = match deserializer.peek_spanned()? {
+            None => break source.len(),
+            Some((Token::OhYeah, span)) => break span.start,
+            Some((_, span)) => span,
+        };
+        let start = line_start(source, deserializer.offset(), span.start);
+        let (prefix, key) = deserializer.entry_key()?;
+        let key_span = deserializer.last_span();
+        let kind = if prefix.is_some() {
+            deserializer.skip_value(prefix)?;
+            Kind::Flag
+        } else if deserializer.opens_map()? {
+            deserializer.open_block();
+            let block = parse_block(source, deserializer)?;
+            deserializer.close_nested_block()?;
+            Kind::Map(block)
+        } else {
+            let value_start = deserializer.offset();
+            deserializer.skip_value(None)?;
+            let value = deserializer.last_span();
+            let range = value_start..deserializer.offset();
+            value_kind(source, range, value, deserializer.dialect())
+        };
+        entries.push(Entry {
+            key: key.to_owned(),
+            span: start..deserializer.offset(),
+            key_span,
+            kind,
+        });
+    };
+    Ok(Block { entries, end })
+}
+
+/// Where the line holding a token starts, or `after` if the token shares a line
+/// with what came before it
+fn line_start(source: &str, after: usize, token: usize) -> usize {
+    source[after..token]
+        .rfind('\n')
+        .map_or(after, |newline| after + newline + 1)
+}
+
+/// Tells scalars from blocks by relexing the value after a key, where `value`
+/// is the source the deserializer took the value from
+fn value_kind(source: &str, range: Range<usize>, value: Range<usize>, dialect: &Dialect) -> Kind {
+    let tokens: Vec<_> = TsonLexer::in_dialect(&source[range.clone()], Some(dialect)).collect();
+    match tokens.as_slice() {
+        [(Ok(Token::ThatShit), that_shit)] if range.start + that_shit.start == value.start => {
+            Kind::Flag
+        }
+        [.., (Ok(Token::ThatShit), _)] => Kind::Scalar(value),
+        _ => Kind::Other,
+    }
+}
@@ -0,0 +1,775 @@
+use std::{borrow::Cow, fmt};
+
+use serde_core::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de::{DeserializeSeed, EnumAccess, IgnoredAny, SeqAccess, VariantAccess, Visitor},
+    ser::{self, SerializeSeq, SerializeTuple, SerializeTupleStruct, SerializeTupleVariant},
+};
+
+use crate::{
+    SerializerOptions, Value,
+    de::needs_raw_identifier,
+    de::{Error, ErrorKind, Literal, reads_as_scalar, visit_scalar},
+    ser::{self as tson_ser, format_float, non_finite},
+};
+
+// Keys that hold more than one value are written as a single identifier, with
+// tuples, tuple structs and sequences as `(a,b)` and newtype and tuple
+// variants as `Variant(a)`. Sequences of scalars short enough to sit on one
+// line are written the same way as `[a,b]`. Parts that would not read back on
+// their own are quoted as `"a b"`, with `\` escaping `"` and `\`
+
+/// The longest a sequence written on one line may be, brackets included
+const INLINE_WIDTH: usize = 64;
+
+/// Whether a part of a key has to be quoted, which includes text that would
+/// otherwise read back as a number or unit where no type says otherwise
+fn needs_quotes(part: &str) -> bool {
+    part.is_empty()
+        || reads_as_scalar(part)
+        || part
+            .chars()
+            .any(|c| c.is_whitespace() || matches!(c, ',' | '(' | ')' | '[' | ']' | '"' | '\\'))
+}
+
+/// Quotes a part as `"..."` with `\` escapes. This only applies inside the
+/// brackets of a key or list; the key as a whole may still be written as raw
+/// text around it
+fn quote(part: &str) -> Cow<'_, str> {
+    if !needs_quotes(part) {
+        return Cow::Borrowed(part);
+    }
+    let mut quoted = String::with_capacity(part.len() + 2);
+    quoted.push('"');
+    for c in part.chars() {
+        if matches!(c, '"' | '\\') {
+            quoted.push('\\');
+        }
+        quoted.push(c);
+    }
+    quoted.push('"');
+    Cow::Owned(quoted)
+}
+
+/// Writes one part of a composite key
+pub(crate) struct KeyPartSerializer<'a> {
+    pub(crate) options: &'a SerializerOptions,
+}
+
+/// Collects the parts of a composite key into its text
+pub(crate) struct CompositeKeySerializer<'a> {
+    options: &'a SerializerOptions,
+    text: String,
+    empty: bool,
+}
+
+impl<'a> CompositeKeySerializer<'a> {
+    /// Starts a tuple, or a variant when named
+    pub(crate) fn new(options: &'a SerializerOptions, variant: Option<&str>) -> Self {
+        let mut text = variant.map(quote).unwrap_or_default().into_owned();
+        text.push('(');
+        Self {
+            options,
+            text,
+            empty: true,
+        }
+    }
+
+    pub(crate) fn part<T: ?Sized + Serialize>(&mut self, value: &T) -> Result<(), tson_ser::Error> {
+        if !std::mem::replace(&mut self.empty, false) {
+            self.text.push(',');
+        }
+        let part = value.serialize(KeyPartSerializer {
+            options: self.options,
+        })?;
+        self.text.push_str(&part);
+        Ok(())
+    }
+
+    pub(crate) fn finish(mut self) -> String {
+        self.text.push(')');
+        self.text
+    }
+}
+
+macro_rules! serialize_part {
+    ($fn_name:ident, $v:ty) => {
+        fn $fn_name(self, v: $v) -> Result<String, tson_ser::Error> {
+            Ok(v.to_string())
+        }
+    };
+}
+
+macro_rules! serialize_float_part {
+    ($fn_name:ident, $v:ty) => {
+        fn $fn_name(self, v: $v) -> Result<String, tson_ser::Error> {
+            if v.is_finite() {
+                Ok(v.to_string())
+            } else if self.options.non_finite_floats {
+                Ok(non_finite(v.into()).to_owned())
+            } else {
+                Err(tson_ser::Error::FloatMustBeFinite)
+            }
+        }
+    };
+}
+
+impl<'a> Serializer for KeyPartSerializer<'a> {
+    type Ok = String;
+    type Error = tson_ser::Error;
+    type SerializeSeq = CompositeKeySerializer<'a>;
+    type SerializeTuple = CompositeKeySerializer<'a>;
+    type SerializeTupleStruct = CompositeKeySerializer<'a>;
+    type SerializeTupleVariant = CompositeKeySerializer<'a>;
+    type SerializeMap = ser::Impossible<String, tson_ser::Error>;
+    type SerializeStruct = ser::Impossible<String, tson_ser::Error>;
+    type SerializeStructVariant = ser::Impossible<String, tson_ser::Error>;
+
+    serialize_part!(serialize_bool, bool);
+    serialize_part!(serialize_i8, i8);
+    serialize_part!(serialize_i16, i16);
+    serialize_part!(serialize_i32, i32);
+    serialize_part!(serialize_i64, i64);
+    serialize_part!(serialize_i128, i128);
+    serialize_part!(serialize_u8, u8);
+    serialize_part!(serialize_u16, u16);
+    serialize_part!(serialize_u32, u32);
+    serialize_part!(serialize_u64, u64);
+    serialize_part!(serialize_u128, u128);
+    serialize_float_part!(serialize_f32, f32);
+    serialize_float_part!(serialize_f64, f64);
+
+    fn serialize_char(self, v: char) -> Result<String, tson_ser::Error> {
+        self.serialize_str(v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<String, tson_ser::Error> {
+        Ok(quote(v).into_owned())
+    }
+
+    fn serialize_bytes(self, _v: &[u8]) -> Result<String, tson_ser::Error> {
+        Err(tson_ser::Error::KeyMustBeStringable)
+    }
+
+    fn serialize_none(self) -> Result<String, tson_ser::Error> {
+        Err(tson_ser::Error::KeyMustBeStringable)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<String, tson_ser::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<String, tson_ser::Error> {
+        Ok(String::from("unit"))
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<String, tson_ser::Error> {
+        self.serialize_str(name)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<String, tson_ser::Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<String, tson_ser::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<String, tson_ser::Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let mut composite = CompositeKeySerializer::new(self.options, Some(variant));
+        composite.part(value)?;
+        Ok(composite.finish())
+    }
+
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, tson_ser::Error> {
+        Ok(CompositeKeySerializer::new(self.options, None))
+    }
+
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, tson_ser::Error> {
+        Ok(CompositeKeySerializer::new(self.options, None))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, tson_ser::Error> {
+        Ok(CompositeKeySerializer::new(self.options, None))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, tson_ser::Error> {
+        Ok(CompositeKeySerializer::new(self.options, Some(variant)))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, tson_ser::Error> {
+        Err(tson_ser::Error::KeyMustBeStringable)
+    }
+
+    fn serialize_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStruct, tson_ser::Error> {
+        Err(tson_ser::Error::KeyMustBeStringable)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, tson_ser::Error> {
+        Err(tson_ser::Error::KeyMustBeStringable)
+    }
+}
+
+macro_rules! serialize_composite {
+    ($trait:ident, $fn_name:ident) => {
+        impl $trait for CompositeKeySerializer<'_> {
+            type Ok = String;
+            type Error = tson_ser::Error;
+
+            fn $fn_name<T>(&mut self, value: &T) -> Result<(), tson_ser::Error>
+            where
+                T: ?Sized + Serialize,
+            {
+                self.part(value)
+            }
+
+            fn end(self) -> Result<String, tson_ser::Error> {
+                Ok(self.finish())
+            }
+        }
+    };
+}
+
+serialize_composite!(SerializeSeq, serialize_element);
+serialize_composite!(SerializeTuple, serialize_element);
+serialize_composite!(SerializeTupleStruct, serialize_field);
+serialize_composite!(SerializeTupleVariant, serialize_field);
+
+/// Whether a value is a sequence written on one line
+pub(crate) fn is_list(text: &str) -> bool {
+    text.len() >= 2 && text.starts_with('[') && text.ends_with(']')
+}
+
+/// Whether a value read without a type is a sequence written on one line: a
+/// single word that reads as `[a,b]` all the way through. A list of one element
+/// is left as text, so that prose such as `[draft]` stays a string
+pub(crate) fn reads_as_list(text: &str, non_finite_floats: bool) -> bool {
+    if !is_list(text) || text.contains(char::is_whitespace) {
+        return false;
+    }
+    let mut list = KeyDeserializer::new(text, non_finite_floats);
+    list.deserialize_list(ElementCount)
+        .is_ok_and(|len| len != 1)
+        && list.end().is_ok()
+}
+
+/// Reads a composite key kept as text in a [`Value`] back into the parts it
+/// was written from, along with the variant it names, if any
+pub(crate) fn composite_parts(text: &str) -> Result<(Option<String>, Vec<Value>), Error> {
+    let mut key = KeyDeserializer::new(text, true);
+    let variant = match key.rest.starts_with('(') {
+        true => None,
+        false => Some(key.part()?.into_owned()),
+    };
+    let parts = Vec::deserialize(&mut key)?;
+    key.end()?;
+    Ok((variant, parts))
+}
+
+/// Counts the elements of a sequence, skipping over what they hold
+struct ElementCount;
+
+impl<'de> Visitor<'de> for ElementCount {
+    type Value = usize;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("a sequence")
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<usize, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut len = 0;
+        while seq.next_element::<IgnoredAny>()?.is_some() {
+            len += 1;
+        }
+        Ok(len)
+    }
+}
+
+/// A sequence element that may be written on one line with the others, held
+/// on to until it is known whether they all fit
+pub(crate) enum Scalar {
+    Int(i128),
+    UInt(u128),
+    F32(f32),
+    F64(f64),
+    Str(String),
+    Unit,
+    UnitStruct(&'static str),
+    UnitVariant(&'static str),
+}
+
+impl Scalar {
+    /// The element as written inside `[...]`, or `None` for a float the
+    /// options don't allow
+    fn part(&self, options: &SerializerOptions) -> Option<String> {
+        let mut buffer = zmij::Buffer::new();
+        Some(match self {
+            Scalar::Int(v) => itoa::Buffer::new().format(*v).to_owned(),
+            Scalar::UInt(v) => itoa::Buffer::new().format(*v).to_owned(),
+            Scalar::F32(v) => format_float(*v, &mut buffer, options).ok()?.to_owned(),
+            Scalar::F64(v) => format_float(*v, &mut buffer, options).ok()?.to_owned(),
+            Scalar::Str(v) => quote(v).into_owned(),
+            Scalar::Unit => String::from("unit"),
+            Scalar::UnitStruct(name) | Scalar::UnitVariant(name) => quote(name).into_owned(),
+        })
+    }
+}
+
+/// Writes a sequence of scalars as `[a,b]`, or returns `None` if it would not
+/// fit on one line or read back as a single word. A single element is left to
+/// a block, as `[a]` reads back as text when read without a type
+pub(crate) fn inline_list(scalars: &[Scalar], options: &SerializerOptions) -> Option<String> {
+    if scalars.len() == 1 {
+        return None;
+    }
+    let mut list = String::from("[");
+    for (i, scalar) in scalars.iter().enumerate() {
+        if i > 0 {
+            list.push(',');
+        }
+        list.push_str(&scalar.part(options)?);
+        if list.len() >= INLINE_WIDTH {
+            return None;
+        }
+    }
+    list.push(']');
+    let dialect = options.dialect.as_ref();
+    (list.len() <= INLINE_WIDTH && !needs_raw_identifier(&list, dialect)).then_some(list)
+}
+
+/// Writes the element the way [`tson_ser::TsonSerializer`] would have
+impl Serialize for Scalar {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Scalar::Int(v) => serializer.serialize_i128(*v),
+            Scalar::UInt(v) => serializer.serialize_u128(*v),
+            Scalar::F32(v) => serializer.serialize_f32(*v),
+            Scalar::F64(v) => serializer.serialize_f64(*v),
+            Scalar::Str(v) => serializer.serialize_str(v),
+            Scalar::Unit => serializer.serialize_unit(),
+            Scalar::UnitStruct(name) => serializer.serialize_unit_struct(name),
+            Scalar::UnitVariant(variant) => serializer.serialize_unit_variant("", 0, variant),
+        }
+    }
+}
+
+/// Reads the parts back out of a key's text, or a sequence's written on one
+/// line
+pub(crate) struct KeyDeserializer<'de> {
+    key: &'de str,
+    rest: &'de str,
+    non_finite_floats: bool,
+}
+
+impl<'de> KeyDeserializer<'de> {
+    pub(crate) fn new(key: &'de str, non_finite_floats: bool) -> Self {
+        Self {
+            key,
+            rest: key,
+            non_finite_floats,
+        }
+    }
+
+    /// Fails unless the whole key was read
+    pub(crate) fn end(&self) -> Result<(), Error> {
+        if self.rest.is_empty() {
+            return Ok(());
+        }
+        Err(self.invalid("the end of the key"))
+    }
+
+    #[cold]
+    fn invalid(&self, expected: &str) -> Error {
+        ErrorKind::InvalidValue(self.key.to_owned(), expected.to_owned()).into()
+    }
+
+    /// Reads a sequence written on one line as `[a,b]`
+    pub(crate) fn deserialize_list<V>(&mut self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.parts('[', ']', visitor)
+    }
+
+    fn parts<V>(&mut self, open: char, close: char, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.punctuation(open)?;
+        let value = visitor.visit_seq(KeyParts {
+            deserializer: &mut *self,
+            close,
+            first: true,
+        })?;
+        self.punctuation(close)?;
+        Ok(value)
+    }
+
+    /// Reads an unquoted part up to the punctuation after it
+    fn atom(&mut self) -> &'de str {
+        let end = (self.rest)
+            .find([',', '(', ')', '[', ']'])
+            .unwrap_or(self.rest.len());
+        let (atom, rest) = self.rest.split_at(end);
+        self.rest = rest;
+        atom
+    }
+
+    fn part(&mut self) -> Result<Cow<'de, str>, Error> {
+        let Some(quoted) = self.rest.strip_prefix('"') else {
+            return Ok(Cow::Borrowed(self.atom()));
+        };
+
+        let mut part = String::new();
+        let mut chars = quoted.char_indices();
+        while let Some((i, c)) = chars.next() {
+            match c {
+                '"' => {
+                    self.rest = &quoted[i + 1..];
+                    return Ok(Cow::Owned(part));
+                }
+                '\\' => part.extend(chars.next().map(|(_, c)| c)),
+                c => part.push(c),
+            }
+        }
+        Err(self.invalid("a closing `\"`"))
+    }
+
+    fn punctuation(&mut self, c: char) -> Result<(), Error> {
+        match self.rest.strip_prefix(c) {
+            Some(rest) => {
+                self.rest = rest;
+                Ok(())
+            }
+            None => Err(self.invalid(&format!("`{c}`"))),
+        }
+    }
+
+    fn literal<T: Literal>(&mut self, expected: &str) -> Result<T, Error> {
+        let part = self.part()?;
+        T::parse_literal(&part)
+            .filter(|v| v.is_finite() || self.non_finite_floats)
+            .ok_or_else(|| ErrorKind::InvalidValue(part.into_owned(), expected.to_owned()).into())
+    }
+}
+
+macro_rules! deserialize_part {
+    ($fn_name:ident, $visitor:ident, $expected:expr) => {
+        fn $fn_name<V>(self, visitor: V) -> Result<V::Value, Error>
+        where
+            V: Visitor<'de>,
+        {
+            let value = self.literal($expected)?;
+            visitor.$visitor(value)
+        }
+    };
+}
+
+impl<'de> Deserializer<'de> for &mut KeyDeserializer<'de> {
+    type Error = Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.rest.chars().next() {
+            Some('(') => self.deserialize_seq(visitor),
+            Some('[') => self.deserialize_list(visitor),
+            Some('"') => self.deserialize_str(visitor),
+            _ => visit_scalar(self.atom(), self.non_finite_floats, visitor),
+        }
+    }
+
+    deserialize_part!(deserialize_i8, visit_i8, "i8");
+    deserialize_part!(deserialize_i16, visit_i16, "i16");
+    deserialize_part!(deserialize_i32, visit_i32, "i32");
+    deserialize_part!(deserialize_i64, visit_i64, "i64");
+    deserialize_part!(deserialize_i128, visit_i128, "i128");
+    deserialize_part!(deserialize_u8, visit_u8, "u8");
+    deserialize_part!(deserialize_u16, visit_u16, "u16");
+    deserialize_part!(deserialize_u32, visit_u32, "u32");
+    deserialize_part!(deserialize_u64, visit_u64, "u64");
+    deserialize_part!(deserialize_u128, visit_u128, "u128");
+    deserialize_part!(deserialize_f32, visit_f32, "f32");
+    deserialize_part!(deserialize_f64, visit_f64, "f64");
+    deserialize_part!(deserialize_char, visit_char, "char");
+
+    fn deserialize_bool<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match &*self.part()? {
+            "true" => visitor.visit_bool(true),
+            "false" => visitor.visit_bool(false),
+            part => {
+                Err(ErrorKind::InvalidValue(part.to_owned(), String::from("true, false")).into())
+            }
+        }
+    }
+
+    fn deserialize_str<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.part()? {
+            Cow::Borrowed(part) => visitor.visit_borrowed_str(part),
+            Cow::Owned(part) => visitor.visit_string(part),
+        }
+    }
+
+    fn deserialize_string<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_bytes<V>(self, _visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        Err(ErrorKind::KeyMustBeStringable.into())
+    }
+
+    fn deserialize_byte_buf<V>(self, _visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        Err(ErrorKind::KeyMustBeStringable.into())
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_unit<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_unit_struct("unit", visitor)
+    }
+
+    fn deserialize_unit_struct<V>(self, name: &'static str, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        let part = self.part()?;
+        if part != name {
+            return Err(ErrorKind::InvalidType(part.into_owned(), String::from(name)).into());
+        }
+        visitor.visit_unit()
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.parts('(', ')', visitor)
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V>(self, _visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        Err(ErrorKind::KeyMustBeStringable.into())
+    }
+
+    fn deserialize_struct<V>(
+        self,
+        _name: &'static str,
+        _fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        Err(ErrorKind::KeyMustBeStringable.into())
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_enum(self)
+    }
+
+    fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_str(visitor)
+    }
+
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.part()?;
+        if self.rest.starts_with('(') {
+            self.deserialize_seq(IgnoredAny)?;
+        } else if self.rest.starts_with('[') {
+            self.deserialize_list(IgnoredAny)?;
+        }
+        visitor.visit_unit()
+    }
+}
+
+struct KeyParts<'a, 'de> {
+    deserializer: &'a mut KeyDeserializer<'de>,
+    close: char,
+    first: bool,
+}
+
+impl<'de> SeqAccess<'de> for KeyParts<'_, 'de> {
+    type Error = Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        if self.deserializer.rest.starts_with(self.close) {
+            return Ok(None);
+        }
+        if !std::mem::replace(&mut self.first, false) {
+            self.deserializer.punctuation(',')?;
+        }
+        seed.deserialize(&mut *self.deserializer).map(Some)
+    }
+}
+
+impl<'de> EnumAccess<'de> for &mut KeyDeserializer<'de> {
+    type Error = Error;
+    type Variant = Self;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, Self), Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(&mut *self)?;
+        Ok((variant, self))
+    }
+}
+
+impl<'de> VariantAccess<'de> for &mut KeyDeserializer<'de> {
+    type Error = Error;
+
+    fn unit_variant(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        self.punctuation('(')?;
+        let value = seed.deserialize(&mut *self)?;
+        self.punctuation(')')?;
+        Ok(value)
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Error>
+    where
+        V: Visitor<'de>,
+    {
+        Err(ErrorKind::KeyMustBeStringable.into())
+    }
+}
@@ -1,19 +1,129 @@
-use serde_core::{Deserialize, Serialize};
+use std::io::{Read, Write};
+
+use serde_core::{Deserialize, Serialize, de::DeserializeOwned};
 
 use crate::{de::TsonDeserializer, ser::TsonSerializer};
 
+mod base64;
 pub mod de;
+pub mod dialect;
+pub mod document;
+mod key;
 pub mod ser;
+pub mod value;
 
-pub fn to_string<T: Serialize>(value: &T) -> Result<String, crate::ser::Error> {
+pub use de::DeserializerOptions;
+pub use dialect::Dialect;
+pub use document::Document;
+pub use ser::{BlankLines, SerializerOptions};
+pub use value::{Value, from_value, to_value};
+
+pub fn to_writer<W: Write, T: Serialize>(writer: W, value: &T) -> Result<(), crate::ser::Error> {
+    let mut serializer = TsonSerializer::new(writer);
+    value.serialize(&mut serializer)?;
+    Ok(())
+}
+
+pub fn to_vec<T: Serialize>(value: &T) -> Result<Vec<u8>, crate::ser::Error> {
     let mut serializer = TsonSerializer::new(Vec::new());
     value.serialize(&mut serializer)?;
-    Ok(unsafe { String::from_utf8_unchecked(serializer.into_inner()) })
+    Ok(serializer.into_inner())
+}
+
+pub fn to_string<T: Serialize>(value: &T) -> Result<String, crate::ser::Error> {
+    Ok(unsafe { String::from_utf8_unchecked(to_vec(value)?) })
+}
+
+pub fn to_writer_with<W: Write, T: Serialize>(
+    writer: W,
+    value: &T,
+    options: &SerializerOptions,
+) -> Result<(), crate::ser::Error> {
+    let mut serializer = TsonSerializer::with_options(writer, options.clone());
+    value.serialize(&mut serializer)?;
+    Ok(())
+}
+
+pub fn to_vec_with<T: Serialize>(
+    value: &T,
+    options: &SerializerOptions,
+) -> Result<Vec<u8>, crate::ser::Error> {
+    let mut serializer = TsonSerializer::with_options(Vec::new(), options.clone());
+    value.serialize(&mut serializer)?;
+    Ok(serializer.into_inner())
+}
+
+pub fn to_string_with<T: Serialize>(
+    value: &T,
+    options: &SerializerOptions,
+) -> Result<String, crate::ser::Error> {
+    Ok(unsafe { String::from_utf8_unchecked(to_vec_with(value, options)?) })
+}
+
+/// Hashes the canonical form of a value (see [`SerializerOptions::canonical`]),
+/// so equal values hash the same whatever order their maps iterate in. Sets
+/// are sequences and keep their order, so hash a `HashSet` as a `BTreeSet`.
+/// The hash is 64-bit FNV-1a and stays the same across platforms and releases
+pub fn canonical_hash<T: Serialize>(value: &T) -> Result<u64, crate::ser::Error> {
+    let mut serializer =
+        TsonSerializer::with_options(crate::ser::Fnv1a::default(), SerializerOptions::canonical());
+    value.serialize(&mut serializer)?;
+    Ok(serializer.into_inner().0)
+}
+
+/// Writes nested blocks indented by four spaces
+pub fn to_string_pretty<T: Serialize>(value: &T) -> Result<String, crate::ser::Error> {
+    to_string_with(value, &SerializerOptions::pretty())
 }
 
+/// Leading byte-order marks are skipped, and a `#!tson` first line picks the
+/// [`Dialect`] the rest is read in
 pub fn from_str<'a, T: Deserialize<'a>>(str: &'a str) -> Result<T, crate::de::Error> {
-    let mut deserializer = TsonDeserializer::new(str);
-    T::deserialize(&mut deserializer)
+    from_str_with(str, &DeserializerOptions::default())
+}
+
+/// Reads a document as [`from_str`] would, with the checks `options` asks for
+pub fn from_str_with<'a, T: Deserialize<'a>>(
+    str: &'a str,
+    options: &DeserializerOptions,
+) -> Result<T, crate::de::Error> {
+    let str = str.strip_prefix('\u{feff}').unwrap_or(str);
+    let (dialect, start) = Dialect::detect(str)?;
+    read(TsonDeserializer::with_dialect(str, start, dialect).with_options(options.clone()))
+}
+
+/// Reads a document written in `dialect` that has no `#!tson` line
+pub fn from_str_in<'a, T: Deserialize<'a>>(
+    str: &'a str,
+    dialect: &Dialect,
+) -> Result<T, crate::de::Error> {
+    dialect.validate()?;
+    let str = str.strip_prefix('\u{feff}').unwrap_or(str);
+    read(TsonDeserializer::with_dialect(str, 0, dialect.clone()))
+}
+
+fn read<'a, T: Deserialize<'a>>(
+    mut deserializer: TsonDeserializer<'a>,
+) -> Result<T, crate::de::Error> {
+    let value = T::deserialize(&mut deserializer).map_err(|err| deserializer.locate(err))?;
+    deserializer.end()?;
+    Ok(value)
+}
+
+/// Validates the bytes as UTF-8, then reads them as [`from_str`] would
+pub fn from_slice<'a, T: Deserialize<'a>>(bytes: &'a [u8]) -> Result<T, crate::de::Error> {
+    let str = std::str::from_utf8(bytes).map_err(|err| crate::de::Error::utf8(bytes, err))?;
+    from_str(str)
+}
+
+/// Reads the reader to the end before deserializing, so only owned types can
+/// be read this way
+pub fn from_reader<R: Read, T: DeserializeOwned>(mut reader: R) -> Result<T, crate::de::Error> {
+    let mut bytes = Vec::new();
+    reader
+        .read_to_end(&mut bytes)
+        .map_err(crate::de::ErrorKind::from)?;
+    from_slice(&bytes)
 }
 
 #[cfg(test)]
@@ -112,4 +222,1985 @@ mod tests {
 
         assert_eq!(to_serialize, from_string);
     }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    #[serde(untagged)]
+    enum Plugin {
+        Http { url: String, port: u16 },
+        Exec { command: String },
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Common {
+        enabled: bool,
+        retries: u8,
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    #[serde(tag = "kind")]
+    enum Source {
+        File { path: String },
+        Inline { body: String },
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct PluginConfig {
+        name: String,
+        #[serde(flatten)]
+        common: Common,
+        plugins: Vec<Plugin>,
+        source: Source,
+        color: Rgb,
+    }
+
+    #[test]
+    fn self_describing_test() {
+        let to_serialize = PluginConfig {
+            name: String::from("scraper"),
+            common: Common {
+                enabled: false,
+                retries: 3,
+            },
+            plugins: vec![
+                Plugin::Http {
+                    url: String::from("http://localhost"),
+                    port: 8080,
+                },
+                Plugin::Exec {
+                    command: String::from("ls -la"),
+                },
+            ],
+            source: Source::File {
+                path: String::from("/etc/scraper"),
+            },
+            color: Rgb::Rgb(1, 2, 3),
+        };
+        let string = crate::to_string(&to_serialize).unwrap();
+        let from_string: PluginConfig = crate::from_str(&string).unwrap();
+        assert_eq!(to_serialize, from_string);
+
+        let value: serde_json::Value = crate::from_str(&string).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "name": "scraper",
+                "enabled": false,
+                "retries": 3,
+                "plugins": [
+                    { "url": "http://localhost", "port": 8080 },
+                    { "command": "ls -la" },
+                ],
+                "source": { "kind": "File", "path": "/etc/scraper" },
+                "color": { "Rgb": [1, 2, 3] },
+            })
+        );
+    }
+
+    #[test]
+    fn deserialize_any_test() {
+        let from_string: HashMap<String, serde_json::Value> = crate::from_str(
+            "
+fuckin reembed that shit
+dont fuckin verbose that shit
+in theory db_url that shit
+ratio 0.5 that shit
+offset -3 that shit
+greeting hello there that shit
+ports
+80 that shit
+443 that shit
+oh yeah
+",
+        )
+        .unwrap();
+
+        let mut expected = HashMap::new();
+        expected.insert(String::from("reembed"), serde_json::json!(true));
+        expected.insert(String::from("verbose"), serde_json::json!(false));
+        expected.insert(String::from("db_url"), serde_json::json!(null));
+        expected.insert(String::from("ratio"), serde_json::json!(0.5));
+        expected.insert(String::from("offset"), serde_json::json!(-3));
+        expected.insert(String::from("greeting"), serde_json::json!("hello there"));
+        expected.insert(String::from("ports"), serde_json::json!([80, 443]));
+        assert_eq!(expected, from_string);
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Eq)]
+    struct OldCli {
+        log_level: String,
+        embedding_model: EmbeddingModel,
+        reembed: bool,
+        other_service: ServiceType,
+    }
+
+    #[test]
+    fn ignored_any_test() {
+        let from_string: OldCli = crate::from_str(
+            "
+log_level info that shit
+in theory db_url that shit
+notes Captains log:
+making the dumbest shit imaginable. that shit
+dont fuckin verbose that shit
+
+embedding_model
+url http://host.docker.internal/v1 that shit
+dimensions 768 that shit
+model embeddinggemma-vllm that shit
+oh yeah
+
+fuckin reembed that shit
+
+service_type main
+auth_service web-auth that shit
+oh yeah
+
+color Rgb
+127 that shit
+255 that shit
+100 that shit
+oh yeah
+
+plugins
+
+url http://localhost that shit
+port 8080 that shit
+oh yeah
+
+oh yeah
+
+other_service auth that shit
+",
+        )
+        .unwrap();
+
+        assert_eq!(
+            from_string,
+            OldCli {
+                log_level: String::from("info"),
+                embedding_model: EmbeddingModel {
+                    url: String::from("http://host.docker.internal/v1"),
+                    model: String::from("embeddinggemma-vllm"),
+                },
+                reembed: true,
+                other_service: ServiceType::Auth,
+            }
+        );
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Listener {
+        host: String,
+        port: u16,
+        addr: SocketAddr,
+    }
+
+    #[test]
+    fn error_location_test() {
+        let err = crate::from_str::<Listener>("host localhost that shit\nport abc that shit\n")
+            .unwrap_err();
+        let location = err.location().unwrap();
+        assert_eq!((location.line, location.column), (2, 6));
+        assert_eq!(err.span(), Some(30..33));
+        assert_eq!(
+            err.to_string(),
+            "invalid value: abc, expected u16 at line 2, column 6"
+        );
+
+        let err = crate::from_str::<Listener>(
+            "host localhost that shit\nport 80 that shit\naddr   nowhere at all that shit\n",
+        )
+        .unwrap_err();
+        let location = err.location().unwrap();
+        assert_eq!((location.line, location.column), (3, 8));
+
+        let err = crate::from_str::<Listener>("host localhost that shit\nport 80 that shit\n")
+            .unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            crate::de::ErrorKind::MissingField("addr")
+        ));
+        assert_eq!(err.location().unwrap().line, 2);
+
+        let err = crate::from_str::<Listener>("host localhost").unwrap_err();
+        assert!(matches!(err.kind(), crate::de::ErrorKind::UnexpectedEof));
+        assert_eq!(err.span(), Some(14..14));
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+    struct Note {
+        note: String,
+        initial: char,
+    }
+
+    #[test]
+    fn raw_text_test() {
+        for text in [
+            "we dont do that shit here",
+            "that shit",
+            "oh yeah",
+            "dont",
+            "in theory",
+            "in fuckin theory it works",
+            "fuckin",
+            "fuckin awesome",
+            "totally fuckin",
+            "",
+            "   ",
+            " padded ",
+            "\ntrailing newline\n",
+            "Captains\nlog",
+            "#\"already raw\"#",
+            "quote \"# and \"## inside",
+            "that\tfuckin\nshit",
+        ] {
+            let to_serialize = Note {
+                note: String::from(text),
+                initial: ' ',
+            };
+            let string = crate::to_string(&to_serialize).unwrap();
+            let from_string: Note = crate::from_str(&string).unwrap();
+            assert_eq!(to_serialize, from_string);
+
+            let value: serde_json::Value = crate::from_str(&string).unwrap();
+            assert_eq!(value, serde_json::json!({ "note": text, "initial": " " }));
+        }
+
+        assert_eq!(
+            crate::to_string(&Note {
+                note: String::from("plain old text"),
+                initial: 'p',
+            })
+            .unwrap(),
+            "note plain old text that shit\ninitial p that shit\n"
+        );
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+    enum Verdict {
+        Dont,
+        #[serde(rename = "dont")]
+        LowerDont,
+        #[serde(rename = "in theory")]
+        InTheory,
+        #[serde(rename = "that")]
+        That(u8, u8),
+        #[serde(rename = "oh")]
+        Oh {
+            yeah: bool,
+        },
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+    struct Keywords {
+        #[serde(rename = "in")]
+        input: String,
+        #[serde(rename = "that")]
+        that: u8,
+        #[serde(rename = "oh")]
+        oh: String,
+        #[serde(rename = "fuckin")]
+        fuckin: bool,
+        #[serde(rename = "dont")]
+        dont: Option<u8>,
+        #[serde(rename = "two words")]
+        two_words: bool,
+        verdicts: Vec<Verdict>,
+        lookup: HashMap<String, u8>,
+    }
+
+    #[test]
+    fn keyword_identifier_test() {
+        let mut lookup = HashMap::new();
+        lookup.insert(String::from(""), 0);
+        lookup.insert(String::from("dont"), 1);
+        lookup.insert(String::from("a b"), 2);
+        lookup.insert(String::from("that"), 3);
+        let to_serialize = Keywords {
+            input: String::from("stdin"),
+            that: 5,
+            oh: String::from("yeah"),
+            fuckin: false,
+            dont: None,
+            two_words: true,
+            verdicts: vec![
+                Verdict::Dont,
+                Verdict::LowerDont,
+                Verdict::InTheory,
+                Verdict::That(1, 2),
+                Verdict::Oh { yeah: true },
+            ],
+            lookup,
+        };
+        let string = crate::to_string(&to_serialize).unwrap();
+        let from_string: Keywords = crate::from_str(&string).unwrap();
+        assert_eq!(to_serialize, from_string);
+    }
+
+    #[test]
+    fn malformed_input_test() {
+        let err = crate::from_str::<Note>("note #\"never closed that shit\ninitial n that shit\n")
+            .unwrap_err();
+        assert!(matches!(err.kind(), crate::de::ErrorKind::InvalidToken(token) if token == "#\""));
+        assert_eq!(err.span(), Some(5..7));
+
+        let err =
+            crate::from_str::<HashMap<String, String>>("a b that shit\nc #\"oops").unwrap_err();
+        assert!(matches!(err.kind(), crate::de::ErrorKind::InvalidToken(_)));
+
+        let from_string: HashMap<String, String> =
+            crate::from_str("note dont panic, oh yeah that shit\nempty  that shit\n").unwrap();
+        assert_eq!(from_string["note"], "dont panic, oh yeah");
+        assert_eq!(from_string["empty"], "");
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Deployment {
+        name: String,
+        replicas: u8,
+        offset: i32,
+        ratio: f64,
+        debug: bool,
+        db_url: Option<String>,
+        embedding_model: EmbeddingModel,
+        service_type: ServiceType,
+        color: Rgb,
+        ports: Vec<u16>,
+        true_map: HashMap<u64, bool>,
+    }
+
+    #[test]
+    fn value_test() {
+        use crate::{Value, value::Number};
+
+        let mut true_map = HashMap::new();
+        true_map.insert(42, true);
+        let deployment = Deployment {
+            name: String::from("web"),
+            replicas: 3,
+            offset: -2,
+            ratio: 0.5,
+            debug: false,
+            db_url: None,
+            embedding_model: EmbeddingModel {
+                url: String::from("http://host.docker.internal/v1"),
+                model: String::from("embeddinggemma-vllm"),
+            },
+            service_type: ServiceType::Main {
+                auth_service: String::from("web-auth"),
+            },
+            color: Rgb::Rgb(1, 2, 3),
+            ports: vec![80, 443],
+            true_map,
+        };
+
+        let string = crate::to_string(&deployment).unwrap();
+        let mut value: Value = crate::from_str(&string).unwrap();
+        assert_eq!(value["name"].as_str(), Some("web"));
+        assert_eq!(value["replicas"], Value::Number(Number::Unsigned(3)));
+        assert_eq!(value["offset"].as_i64(), Some(-2));
+        assert_eq!(value["ratio"].as_f64(), Some(0.5));
+        assert_eq!(value["debug"], Value::Bool(false));
+        assert!(value["db_url"].is_none());
+        assert_eq!(value["ports"][1].as_u64(), Some(443));
+        assert_eq!(value["color"]["Rgb"][2].as_u64(), Some(3));
+        assert!(value["missing"]["deeper"][0].is_none());
+        assert_eq!(value.get("ports").and_then(|ports| ports.get(2)), None);
+
+        value["embedding_model"]["model"] = Value::from("nomic-embed-text");
+        value["db_url"] = Value::from("postgres://localhost");
+        value["replicas"] = Value::from(5u8);
+        let string = crate::to_string(&value).unwrap();
+        let rewritten: Deployment = crate::from_str(&string).unwrap();
+        assert_eq!(rewritten.embedding_model.model, "nomic-embed-text");
+        assert_eq!(rewritten.db_url.as_deref(), Some("postgres://localhost"));
+        assert_eq!(rewritten.replicas, 5);
+        assert_eq!(rewritten.service_type, deployment.service_type);
+
+        let value = crate::to_value(&deployment).unwrap();
+        assert_eq!(
+            value["service_type"]["main"]["auth_service"].as_str(),
+            Some("web-auth")
+        );
+        assert_eq!(value["true_map"]["42"], Value::Bool(true));
+        let from_value: Deployment = crate::from_value(value).unwrap();
+        assert_eq!(deployment, from_value);
+    }
+
+    #[cfg(feature = "serde_json")]
+    #[test]
+    fn value_json_test() {
+        use crate::Value;
+
+        let json = serde_json::json!({
+            "name": "web",
+            "replicas": 3,
+            "offset": -2,
+            "ratio": 0.5,
+            "db_url": null,
+            "ports": [80, 443],
+            "embedding_model": { "url": "http://localhost", "model": "gemma" },
+        });
+        let value = Value::from(json.clone());
+        assert_eq!(value["ports"][0].as_u64(), Some(80));
+        let from_string: Value = crate::from_str(&crate::to_string(&value).unwrap()).unwrap();
+        assert_eq!(from_string, value);
+        assert_eq!(serde_json::Value::from(from_string), json);
+        assert_eq!(
+            serde_json::Value::from(Value::from(u128::MAX)),
+            serde_json::Value::from(u128::MAX.to_string())
+        );
+    }
+
+    #[test]
+    fn untyped_string_test() {
+        use crate::Value;
+
+        // Strings that would read back as numbers or units are written raw
+        let texts = ["5", "-1.5", "0x10", "1_000", "unit", "5 apples"];
+        let value = Value::Map(
+            texts
+                .iter()
+                .map(|text| (text.to_string(), Value::from(*text)))
+                .collect(),
+        );
+        let string = crate::to_string(&value).unwrap();
+        assert!(string.contains("0x10 #\"0x10\"# that shit\n"), "{string}");
+        assert!(string.contains("\"# 5 apples that shit\n"), "{string}");
+        assert_eq!(crate::from_str::<Value>(&string).unwrap(), value);
+        let list = Value::from(vec![
+            Value::from("5"),
+            Value::from(5u8),
+            Value::from("unit"),
+        ]);
+        let string = crate::to_string(&HashMap::from([("list", &list)])).unwrap();
+        assert_eq!(string, "list [\"5\",5,\"unit\"] that shit\n");
+        assert_eq!(crate::from_str::<Value>(&string).unwrap()["list"], list);
+        let typed: HashMap<String, String> =
+            crate::from_str("port #\"8080\"# that shit\n").unwrap();
+        assert_eq!(typed["port"], "8080");
+
+        #[cfg(feature = "serde_json")]
+        {
+            let json = serde_json::json!({ "port": "8080", "none": null, "unit": "unit" });
+            let value: Value = serde_json::from_value(json.clone()).unwrap();
+            assert_eq!(value, Value::from(json.clone()));
+            assert_eq!(value["none"], Value::Unit);
+            let value: Value = crate::from_str(&crate::to_string(&value).unwrap()).unwrap();
+            assert_eq!(serde_json::Value::from(value), json);
+        }
+    }
+
+    #[test]
+    fn reader_writer_test() {
+        let listener = Listener {
+            host: String::from("localhost"),
+            port: 8080,
+            addr: "0.0.0.0:8080".parse().unwrap(),
+        };
+        let string = crate::to_string(&listener).unwrap();
+        let bytes = crate::to_vec(&listener).unwrap();
+        assert_eq!(string.as_bytes(), bytes);
+        let mut written = Vec::new();
+        crate::to_writer(&mut written, &listener).unwrap();
+        assert_eq!(written, bytes);
+
+        let from_slice: Listener = crate::from_slice(&bytes).unwrap();
+        assert_eq!(listener, from_slice);
+        let from_reader: Listener = crate::from_reader(bytes.as_slice()).unwrap();
+        assert_eq!(listener, from_reader);
+
+        let mut with_bom = b"\xEF\xBB\xBF".to_vec();
+        with_bom.extend_from_slice(&bytes);
+        let from_slice: Listener = crate::from_slice(&with_bom).unwrap();
+        assert_eq!(listener, from_slice);
+        let from_reader: Listener = crate::from_reader(with_bom.as_slice()).unwrap();
+        assert_eq!(listener, from_reader);
+
+        let err =
+            crate::from_slice::<Listener>(b"host local\xFFhost that shit\nport 80 that shit\n")
+                .unwrap_err();
+        assert!(matches!(err.kind(), crate::de::ErrorKind::Utf8(_)));
+        assert_eq!(err.span(), Some(10..11));
+        assert_eq!(err.location().unwrap().column, 11);
+    }
+
+    #[test]
+    fn trailing_tokens_test() {
+        let source = "host localhost that shit\nport 80 that shit\naddr 0.0.0.0:80 that shit\n";
+        let listener: Listener = crate::from_str(source).unwrap();
+        assert_eq!(listener.port, 80);
+
+        let err = crate::from_str::<Listener>(&format!("{source}oh yeah\n")).unwrap_err();
+        assert!(matches!(err.kind(), crate::de::ErrorKind::TrailingTokens));
+        assert_eq!(err.location().unwrap().line, 4);
+
+        let err =
+            crate::from_str::<HashMap<String, String>>("a b that shit\noh yeah\nc d that shit\n")
+                .unwrap_err();
+        assert!(matches!(err.kind(), crate::de::ErrorKind::TrailingTokens));
+        assert_eq!(err.span(), Some(14..21));
+
+        let err =
+            crate::from_str::<Vec<u8>>("1 that shit\n2 that shit\noh yeah\noh yeah\n").unwrap_err();
+        assert!(matches!(err.kind(), crate::de::ErrorKind::TrailingTokens));
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Meters(f64);
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Point(i32, i32);
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    enum Shape {
+        Empty,
+        Circle(Meters),
+        Named(String),
+        Labeled(EmbeddingModel),
+        Rect(u8, u8),
+        Polygon { points: Vec<Point>, closed: bool },
+    }
+
+    /// Every layout the serializer can be asked for
+    fn layouts() -> [crate::ser::SerializerOptions; 4] {
+        use crate::ser::{BlankLines, SerializerOptions};
+
+        [
+            SerializerOptions::default(),
+            SerializerOptions::pretty(),
+            SerializerOptions::compact().indent("\t"),
+            SerializerOptions::new()
+                .indent("  ")
+                .blank_lines(BlankLines::Entries)
+                .intensify_true(false)
+                .omit_none(true),
+        ]
+    }
+
+    /// A dialect where every keyword differs from TSON's
+    fn terse() -> crate::Dialect {
+        use std::borrow::Cow;
+
+        crate::Dialect {
+            that_shit: Cow::Borrowed("ok"),
+            oh_yeah: Cow::Borrowed("end of list"),
+            dont: Cow::Borrowed("not"),
+            in_theory: Cow::Borrowed("none"),
+            fuckin: Cow::Borrowed("so"),
+            psst: Cow::Borrowed("note"),
+            shut_the_fuck_up: Cow::Borrowed("quiet"),
+            go_on: Cow::Borrowed("resume"),
+        }
+    }
+
+    fn round_trip<T>(value: T)
+    where
+        T: Serialize + serde::de::DeserializeOwned + PartialEq + std::fmt::Debug,
+    {
+        for options in layouts() {
+            round_trip_with(&value, &options);
+        }
+    }
+
+    /// Round-trips a value through one layout, for options tested on their own
+    fn round_trip_with<T>(value: &T, options: &crate::ser::SerializerOptions)
+    where
+        T: Serialize + serde::de::DeserializeOwned + PartialEq + std::fmt::Debug,
+    {
+        let string = crate::to_string_with(value, options).unwrap();
+        let from_string: T = crate::from_str(&string).unwrap_or_else(|err| {
+            panic!("{err} while reading back {value:?} with {options:?} from:\n{string}")
+        });
+        assert_eq!(*value, from_string, "with {options:?} from:\n{string}");
+    }
+
+    #[test]
+    fn top_level_test() {
+        assert_eq!(crate::to_string(&5u32).unwrap(), "5 that shit\n");
+        round_trip(5u32);
+        round_trip(-7i64);
+        round_trip(1.5f64);
+        round_trip(true);
+        round_trip(false);
+        round_trip('x');
+        round_trip(String::from("hello"));
+        round_trip(String::from("two words\nand a line"));
+        round_trip(String::from("that shit"));
+        round_trip(String::new());
+        round_trip(None::<u8>);
+        round_trip(Some(3u8));
+        round_trip(Some(false));
+        round_trip(());
+        round_trip(Check);
+        round_trip(Meters(2.5));
+        round_trip(Point(1, -1));
+        round_trip((1u8, String::from("a b")));
+        round_trip(vec![1u8, 2, 3]);
+        round_trip(Vec::<u8>::new());
+        round_trip(vec![vec![1u8, 2], vec![3]]);
+        round_trip(vec![
+            EmbeddingModel {
+                url: String::from("a"),
+                model: String::from("b"),
+            },
+            EmbeddingModel {
+                url: String::from("c"),
+                model: String::from("d"),
+            },
+        ]);
+        round_trip(Shape::Empty);
+        round_trip(Shape::Circle(Meters(1.0)));
+        round_trip(Shape::Named(String::from("blob")));
+        round_trip(Shape::Labeled(EmbeddingModel {
+            url: String::from("a"),
+            model: String::from("b"),
+        }));
+        round_trip(Shape::Rect(2, 3));
+        round_trip(Shape::Polygon {
+            points: vec![Point(0, 0), Point(1, 0), Point(0, 1)],
+            closed: true,
+        });
+        round_trip(vec![
+            Shape::Empty,
+            Shape::Rect(1, 2),
+            Shape::Circle(Meters(3.0)),
+        ]);
+        round_trip(Some(Shape::Rect(4, 5)));
+
+        // A sequence of maps reads back without a type as well
+        let models = vec![
+            EmbeddingModel {
+                url: String::from("a"),
+                model: String::from("b"),
+            },
+            EmbeddingModel {
+                url: String::from("c"),
+                model: String::from("d"),
+            },
+        ];
+        for options in layouts() {
+            let string = crate::to_string_with(&models, &options).unwrap();
+            let value: crate::Value = crate::from_str(&string).unwrap();
+            assert_eq!(
+                crate::from_value::<Vec<EmbeddingModel>>(value).unwrap(),
+                models
+            );
+            crate::from_str::<serde::de::IgnoredAny>(&string).unwrap();
+        }
+        // Entries after a blank line are still the document itself
+        let value: crate::Value =
+            crate::from_str("\nurl a that shit\nmodel b that shit\n").unwrap();
+        assert_eq!(value["model"], crate::Value::from("b"));
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    enum Toggle {
+        Set(bool),
+        Limit(Option<u32>),
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Flags {
+        features: Vec<bool>,
+        sparse: Vec<Option<u8>>,
+        maybe: Vec<Option<bool>>,
+        row: (u8, bool, Option<String>, bool),
+        nested: Vec<Vec<bool>>,
+    }
+
+    #[test]
+    fn positional_prefix_test() {
+        round_trip(vec![true, false, true]);
+        round_trip(vec![Some(1u8), None, Some(3)]);
+        round_trip(vec![Some(true), Some(false), None]);
+        round_trip((false, None::<u8>, Some(String::from("x"))));
+        round_trip(Toggle::Set(false));
+        round_trip(Toggle::Set(true));
+        round_trip(Toggle::Limit(None));
+        round_trip(Toggle::Limit(Some(5)));
+        round_trip(Flags {
+            features: vec![false, true, false],
+            sparse: vec![None, Some(2), None],
+            maybe: vec![None, Some(false), Some(true)],
+            row: (1, false, None, true),
+            nested: vec![vec![false], vec![true, false]],
+        });
+
+        assert_eq!(
+            crate::to_string(&vec![false, true]).unwrap(),
+            "dont fuckin that shit\nfuckin that shit\n"
+        );
+        let value: crate::Value =
+            crate::from_str("a\nin theory that shit\ndont fuckin that shit\noh yeah\n").unwrap();
+        assert!(value["a"][0].is_none());
+        assert_eq!(value["a"][1], crate::Value::Bool(false));
+
+        let value: crate::Value = crate::from_str(
+            &crate::to_string(&vec![vec![Some(false)], vec![None, Some(true)]]).unwrap(),
+        )
+        .unwrap();
+        assert_eq!(value[0][0], crate::Value::Bool(false));
+        assert!(value[1][0].is_none());
+        round_trip(vec![Some(vec![None, Some(1u8)]), None]);
+
+        // A `true` on the line below a key opens a sequence rather than
+        // being the key's text
+        for list in [vec![true], vec![true, false], vec![true, true]] {
+            let map = HashMap::from([(String::from("list"), list.clone())]);
+            for options in layouts() {
+                let string = crate::to_string_with(&map, &options).unwrap();
+                let value: crate::Value = crate::from_str(&string).unwrap();
+                assert_eq!(value, crate::to_value(&map).unwrap(), "{string}");
+                let nested = HashMap::from([("outer", &map)]);
+                let string = crate::to_string_with(&nested, &options).unwrap();
+                let value: crate::Value = crate::from_str(&string).unwrap();
+                assert_eq!(value["outer"]["list"], crate::to_value(&list).unwrap());
+            }
+            let mut document = crate::Document::parse("name web that shit\n").unwrap();
+            document.set(&["list"], &list).unwrap();
+            assert_eq!(
+                document.get(&["list"]),
+                Some(crate::to_value(&list).unwrap())
+            );
+        }
+        let value: crate::Value = crate::from_str("mood\nfuckin that shit\noh yeah\n").unwrap();
+        assert_eq!(value["mood"], crate::Value::from(vec![true]));
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    enum Limit {
+        Max(u32),
+        Scale(f64),
+        Name(String),
+        Maybe(Option<u8>),
+        Flag(bool),
+        Items(Vec<u8>),
+        Model(EmbeddingModel),
+        Shape(Shape),
+        Point(Point),
+        Unit(()),
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Limits {
+        limit: Limit,
+        after: u8,
+        all: Vec<Limit>,
+        named: HashMap<String, Limit>,
+    }
+
+    #[test]
+    fn newtype_variant_test() {
+        let string = crate::to_string(&Limits {
+            limit: Limit::Max(5),
+            after: 1,
+            all: Vec::new(),
+            named: HashMap::new(),
+        })
+        .unwrap();
+        assert!(string.starts_with("\nlimit Max\n5 that shit\noh yeah\n\nafter 1 that shit\n"));
+
+        let every = || {
+            vec![
+                Limit::Max(5),
+                Limit::Scale(0.25),
+                Limit::Name(String::from("two words")),
+                Limit::Name(String::new()),
+                Limit::Maybe(None),
+                Limit::Maybe(Some(7)),
+                Limit::Flag(false),
+                Limit::Flag(true),
+                Limit::Items(vec![1, 2]),
+                Limit::Model(EmbeddingModel {
+                    url: String::from("a"),
+                    model: String::from("b"),
+                }),
+                Limit::Shape(Shape::Rect(1, 2)),
+                Limit::Shape(Shape::Circle(Meters(1.5))),
+                Limit::Point(Point(3, 4)),
+                Limit::Unit(()),
+            ]
+        };
+        for limit in every() {
+            round_trip(limit);
+        }
+        for ((limit, first), second) in every().into_iter().zip(every()).zip(every()) {
+            round_trip(Limits {
+                limit,
+                after: 9,
+                all: vec![first],
+                named: HashMap::from([(String::from("only"), second)]),
+            });
+        }
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Frames {
+        lists: HashMap<String, Vec<u8>>,
+        models: HashMap<String, EmbeddingModel>,
+        shapes: HashMap<String, Shape>,
+        nested: Vec<HashMap<String, Vec<Vec<u8>>>>,
+        grid: (Vec<u8>, Vec<Vec<u8>>),
+    }
+
+    #[test]
+    fn frame_test() {
+        round_trip(Frames {
+            lists: HashMap::from([(String::new(), vec![1, 2]), (String::from("a"), vec![])]),
+            models: HashMap::from([(
+                String::new(),
+                EmbeddingModel {
+                    url: String::from("a"),
+                    model: String::new(),
+                },
+            )]),
+            shapes: HashMap::from([
+                (String::new(), Shape::Rect(1, 2)),
+                (String::from(" "), Shape::Circle(Meters(2.0))),
+            ]),
+            nested: vec![
+                HashMap::from([(String::new(), vec![vec![1], vec![]])]),
+                HashMap::new(),
+            ],
+            grid: (vec![1], vec![vec![2, 3], vec![4]]),
+        });
+        round_trip(HashMap::from([(String::new(), vec![vec![1u8]])]));
+        round_trip(vec![Vec::<u8>::new(), vec![1], Vec::new()]);
+        round_trip(vec![HashMap::<String, u8>::new(), HashMap::new()]);
+        round_trip((HashMap::<String, u8>::new(), Vec::<u8>::new(), 1u8));
+    }
+
+    #[test]
+    fn comment_test() {
+        let source = r#"psst bumped this for the March incident
+host localhost that shit psst the old one kept falling over
+shut the fuck up
+port used to be 80, see
+the runbook
+go on
+port 8080 that shit
+addr
+0.0.0.0:8080 psst trailing
+that shit
+"#;
+        let listener: Listener = crate::from_str(source).unwrap();
+        assert_eq!(listener.host, "localhost");
+        assert_eq!(listener.port, 8080);
+        assert_eq!(listener.addr, "0.0.0.0:8080".parse().unwrap());
+
+        // A comment may not sit between the words of a value, where it would
+        // otherwise end up in the value
+        for source in [
+            "note first line\npsst an ops note\nlast line that shit\n",
+            "note first shut the fuck up x go on last that shit\n",
+            "note first fuckin psst x\nlast that shit\n",
+        ] {
+            let err = crate::from_str::<HashMap<String, String>>(source).unwrap_err();
+            assert!(
+                matches!(err.kind(), crate::de::ErrorKind::CommentInValue),
+                "{source}"
+            );
+            assert!(
+                source[err.span().unwrap()].starts_with(['p', 's']),
+                "{source}"
+            );
+        }
+        let err =
+            crate::from_str::<crate::Value>("#!tson friendly\nnote a hush x go on b that stuff\n")
+                .unwrap_err();
+        assert!(matches!(err.kind(), crate::de::ErrorKind::CommentInValue));
+        assert_eq!(err.location().unwrap().column, 8);
+        let note: HashMap<String, String> =
+            crate::from_str("note first fuckin line psst after\nthat shit\n").unwrap();
+        assert_eq!(note["note"], "first fuckin line");
+
+        let value: crate::Value = crate::from_str(
+            "models\npsst a note about the first model\nshut the fuck up x go on\n\nurl a that shit\nmodel b that shit\noh yeah\n\noh yeah\n",
+        )
+        .unwrap();
+        assert_eq!(value["models"][0]["url"].as_str(), Some("a"));
+
+        let err = crate::from_str::<Listener>("shut the fuck up\nnever reopened\n").unwrap_err();
+        assert!(matches!(err.kind(), crate::de::ErrorKind::InvalidToken(_)));
+
+        for text in [
+            "psst",
+            "a psst b",
+            "a\npsst b",
+            "psst a",
+            "shut the fuck up",
+            "shut the fuck up go on",
+            "a shut the fuck up b go on",
+            "go on",
+        ] {
+            round_trip(Note {
+                note: String::from(text),
+                initial: 'p',
+            });
+        }
+        round_trip(HashMap::from([
+            (String::from("psst"), 1u8),
+            (String::from("go"), 2),
+        ]));
+    }
+
+    #[test]
+    fn format_test() {
+        use crate::dialect::format;
+
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Service {
+            name: String,
+            id: u128,
+            cert: Blob,
+            notes: String,
+            listen: Listener,
+        }
+
+        let source = "name   auth  that shit\n\n\n\nid 340282366920938463463374607431768211455 that shit\ncert base64:TWFu that shit\nnotes first line\n    second line\tthat shit\n\n  listen\n    port 8080 that   shit\n    host localhost that shit\n    addr 0.0.0.0:8080 that shit\n  oh yeah";
+        let formatted = format(source).unwrap().unwrap();
+        assert_eq!(
+            formatted,
+            "cert base64:TWFu that shit\nid 340282366920938463463374607431768211455 that shit\n\nlisten\naddr 0.0.0.0:8080 that shit\nhost localhost that shit\nport 8080 that shit\noh yeah\n\nname auth that shit\nnotes first line\n    second line that shit\n"
+        );
+        assert_eq!(format(&formatted).unwrap().unwrap(), formatted);
+        let service: Service = crate::from_str(&formatted).unwrap();
+        assert_eq!(service, crate::from_str(source).unwrap());
+        assert_eq!(service.id, u128::MAX);
+
+        // Indented output is rewritten too
+        let pretty =
+            crate::to_string_pretty(&crate::from_str::<crate::Value>(source).unwrap()).unwrap();
+        assert_eq!(format(&pretty).unwrap().unwrap(), formatted);
+
+        let friendly = "#!tson friendly\n  really   reembed that stuff\n";
+        assert_eq!(
+            format(friendly).unwrap().unwrap(),
+            "#!tson friendly\nreally reembed that stuff\n"
+        );
+        assert_eq!(format("").unwrap().unwrap(), "");
+
+        // Comments and the way numbers are written would be lost
+        let commented = "psst bumped this for the March incident\nname auth that shit\n";
+        assert_eq!(format(commented).unwrap(), None);
+        assert_eq!(
+            format("#!tson friendly\nhush a note go on\nreally reembed that stuff\n").unwrap(),
+            None
+        );
+        assert_eq!(format("port 0080 that shit\n").unwrap(), None);
+        assert!(format("a b\n").is_err());
+    }
+
+    #[test]
+    fn document_test() {
+        use crate::{Document, document::Error};
+
+        let source = r#"psst deploy config, edit with care
+name   api that  fuckin shit
+version 1.4.2 that shit
+
+fuckin reembed that shit
+in theory mirror that shit
+
+model
+url    http://localhost:11434 that shit psst local ollama
+model mxbai-embed-large that shit
+oh yeah
+
+shut the fuck up
+ports get opened in order
+go on
+ports
+80 that shit
+443 that shit
+oh yeah
+"#;
+        let changed_lines = |a: &str, b: &str| {
+            assert_eq!(a.lines().count(), b.lines().count());
+            a.lines().zip(b.lines()).filter(|(a, b)| a != b).count()
+        };
+
+        let mut document: Document = source.parse().unwrap();
+        assert_eq!(document.to_string(), source);
+        assert_eq!(document.get(&["version"]).unwrap().as_str(), Some("1.4.2"));
+        assert_eq!(document.get(&["reembed"]).unwrap().as_bool(), Some(true));
+        assert!(document.get(&["mirror"]).unwrap().is_none());
+        assert_eq!(document.get(&["ports"]).unwrap()[1].as_u64(), Some(443));
+        assert_eq!(
+            document.get(&["model", "model"]).unwrap().as_str(),
+            Some("mxbai-embed-large")
+        );
+        assert_eq!(document.get(&[]).unwrap()["name"].as_str(), Some("api"));
+        assert!(document.get(&["model", "missing"]).is_none());
+        assert!(document.contains(&["model", "url"]));
+
+        document.set(&["version"], "1.4.3").unwrap();
+        assert_eq!(changed_lines(source, document.as_str()), 1);
+        assert!(document.as_str().contains("version 1.4.3 that shit\n"));
+
+        let before = document.to_string();
+        document.set(&["name"], "gateway").unwrap();
+        assert_eq!(changed_lines(&before, document.as_str()), 1);
+        assert!(
+            document
+                .as_str()
+                .contains("name   gateway that  fuckin shit\n")
+        );
+
+        let before = document.to_string();
+        document.set(&["reembed"], &false).unwrap();
+        document.set(&["mirror"], &true).unwrap();
+        assert_eq!(changed_lines(&before, document.as_str()), 2);
+        assert!(
+            document
+                .as_str()
+                .contains("dont fuckin reembed that shit\n")
+        );
+        assert!(document.as_str().contains("fuckin mirror that shit\n"));
+
+        let before = document.to_string();
+        document
+            .set(&["model", "url"], "http://10.0.0.2:11434")
+            .unwrap();
+        assert_eq!(changed_lines(&before, document.as_str()), 1);
+        assert!(
+            document
+                .as_str()
+                .contains("url    http://10.0.0.2:11434 that shit psst local ollama\n")
+        );
+
+        document.insert(&["model", "dims"], &1024u32).unwrap();
+        assert!(
+            document
+                .as_str()
+                .contains("model mxbai-embed-large that shit\ndims 1024 that shit\noh yeah\n")
+        );
+        assert!(matches!(
+            document.insert(&["model", "dims"], &512u32),
+            Err(Error::AlreadyExists(_))
+        ));
+        assert!(matches!(
+            document.set(&["version", "major"], &1u8),
+            Err(Error::NotABlock(_))
+        ));
+        assert!(matches!(
+            document.set(&["missing", "key"], &1u8),
+            Err(Error::NotFound(_))
+        ));
+
+        document.set(&["ports"], &[8080u16]).unwrap();
+        assert_eq!(document.get(&["ports"]).unwrap()[0].as_u64(), Some(8080));
+        assert!(document.as_str().contains("ports get opened in order"));
+
+        document
+            .insert(
+                &["listener"],
+                &Listener {
+                    host: String::from("localhost"),
+                    port: 8080,
+                    addr: "0.0.0.0:8080".parse().unwrap(),
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            document.get(&["listener", "port"]).unwrap().as_u64(),
+            Some(8080)
+        );
+
+        assert!(document.remove(&["model", "dims"]).unwrap());
+        assert!(!document.remove(&["model", "dims"]).unwrap());
+        assert!(document.remove(&["mirror"]).unwrap());
+        assert!(!document.as_str().contains("mirror"));
+        assert!(document.as_str().starts_with(
+            "psst deploy config, edit with care\nname   gateway that  fuckin shit\nversion 1.4.3 that shit\n\ndont fuckin reembed that shit\n\nmodel\n"
+        ));
+
+        // Whatever the edits, the result still reads as a whole
+        let value: crate::Value = crate::from_str(document.as_str()).unwrap();
+        assert_eq!(value["listener"]["host"].as_str(), Some("localhost"));
+        assert_eq!(
+            value["model"]["url"].as_str(),
+            Some("http://10.0.0.2:11434")
+        );
+
+        assert!(Document::parse("a b that shit\noh yeah\n").is_err());
+        let mut empty = Document::parse("").unwrap();
+        empty.set(&["a"], "b").unwrap();
+        assert_eq!(empty.as_str(), "a b that shit\n");
+
+        // Edits inside indented blocks keep to the block's indentation
+        let indented = "model\n  url http://localhost that shit\n  fuckin reembed that shit\n  \
+                        limits\n    tokens 512 that shit\n  oh yeah\n  empty\n  oh yeah\noh yeah\n";
+        let mut document: Document = indented.parse().unwrap();
+        document.set(&["model", "reembed"], &false).unwrap();
+        assert!(
+            document
+                .as_str()
+                .contains("\n  dont fuckin reembed that shit\n")
+        );
+        document.set(&["model", "reembed"], &true).unwrap();
+        assert_eq!(document.as_str(), indented);
+        document.insert(&["model", "limits", "batch"], &8).unwrap();
+        assert!(
+            document
+                .as_str()
+                .contains("    tokens 512 that shit\n    batch 8 that shit\n  oh yeah\n")
+        );
+        document.set(&["model", "name"], "mxbai").unwrap();
+        assert!(
+            document
+                .as_str()
+                .contains("\n  name mxbai that shit\noh yeah\n")
+        );
+        document.set(&["model", "empty", "a"], "b").unwrap();
+        assert!(
+            document
+                .as_str()
+                .contains("  empty\n    a b that shit\n  oh yeah\n")
+        );
+        document
+            .set(
+                &["model", "url"],
+                &std::collections::BTreeMap::from([("host", "localhost")]),
+            )
+            .unwrap();
+        assert!(
+            document
+                .as_str()
+                .contains("\n  url\n    host localhost that shit\n  oh yeah\n")
+        );
+        document
+            .insert(&["model", "notes"], "first line\nsecond line")
+            .unwrap();
+        assert_eq!(
+            document.get(&["model", "notes"]).unwrap().as_str(),
+            Some("first line\nsecond line")
+        );
+        assert_eq!(
+            document
+                .get(&["model", "limits", "batch"])
+                .unwrap()
+                .as_u64(),
+            Some(8)
+        );
+    }
+
+    #[derive(Debug, Serialize, Deserialize, PartialEq)]
+    struct Layout {
+        reembed: bool,
+        name: String,
+        db_url: Option<String>,
+        model: EmbeddingModel,
+        ports: Vec<u16>,
+        enabled: bool,
+    }
+
+    #[test]
+    fn options_test() {
+        use crate::{BlankLines, SerializerOptions};
+
+        let layout = || Layout {
+            reembed: true,
+            name: String::from("api"),
+            db_url: None,
+            model: EmbeddingModel {
+                url: String::from("one\ntwo"),
+                model: String::from("b"),
+            },
+            ports: vec![80, 443],
+            enabled: true,
+        };
+        round_trip(layout());
+        let layout = layout();
+
+        assert_eq!(
+            crate::to_string_with(&layout, &SerializerOptions::pretty().inline_seqs(false))
+                .unwrap(),
+            "fuckin reembed that shit\nname api that shit\nin theory db_url that shit\n\nmodel\n    url #\"one\ntwo\"# that shit\n    model b that shit\noh yeah\n\nports\n    80 that shit\n    443 that shit\noh yeah\n\nfuckin enabled that shit\n"
+        );
+        assert_eq!(
+            crate::to_string_with(
+                &layout,
+                &SerializerOptions::compact()
+                    .intensify_true(false)
+                    .omit_none(true)
+                    .inline_seqs(false)
+            )
+            .unwrap(),
+            "fuckin reembed that shit\nname api that shit\nmodel\nurl #\"one\ntwo\"# that shit\nmodel b that shit\noh yeah\nports\n80 that shit\n443 that shit\noh yeah\nenabled that shit\n"
+        );
+        assert_eq!(
+            crate::to_string_with(
+                &EmbeddingModel {
+                    url: String::from("a"),
+                    model: String::from("b"),
+                },
+                &SerializerOptions::new().blank_lines(BlankLines::Entries)
+            )
+            .unwrap(),
+            "url a that shit\n\nmodel b that shit\n"
+        );
+
+        let value: crate::Value = crate::from_str(&crate::to_string(&layout).unwrap()).unwrap();
+        for options in layouts() {
+            let string = crate::to_string_with(&value, &options).unwrap();
+            let from_string: crate::Value = crate::from_str(&string).unwrap();
+            assert_eq!(from_string, value, "from:\n{string}");
+        }
+
+        // Only struct fields are left out, as a map entry holding `None` is
+        // data in its own right
+        let omit_none = SerializerOptions::new().omit_none(true);
+        let map = HashMap::from([(String::from("db_url"), None::<String>)]);
+        let string = crate::to_string_with(&map, &omit_none).unwrap();
+        assert_eq!(string, "in theory db_url that shit\n");
+        let from_string: HashMap<String, Option<String>> = crate::from_str(&string).unwrap();
+        assert_eq!(from_string, map);
+    }
+
+    #[test]
+    fn canonical_test() {
+        use crate::SerializerOptions;
+
+        let forward: HashMap<String, u8> = (0..64).map(|i| (format!("k{i}"), i)).collect();
+        let mut backward = HashMap::with_capacity(1024);
+        backward.extend((0..64).rev().map(|i| (format!("k{i}"), i)));
+        let nested = |map: &HashMap<String, u8>| {
+            vec![
+                HashMap::from([
+                    (String::from("b"), map.clone()),
+                    (String::from("a"), HashMap::new()),
+                ]),
+                HashMap::new(),
+            ]
+        };
+
+        let canonical = SerializerOptions::canonical();
+        let string = crate::to_string_with(&nested(&forward), &canonical).unwrap();
+        assert_eq!(
+            string,
+            crate::to_string_with(&nested(&backward), &canonical).unwrap()
+        );
+        assert_eq!(
+            crate::canonical_hash(&nested(&forward)).unwrap(),
+            crate::canonical_hash(&nested(&backward)).unwrap()
+        );
+        assert_ne!(
+            crate::canonical_hash(&forward).unwrap(),
+            crate::canonical_hash(&nested(&forward)).unwrap()
+        );
+        let from_string: Vec<HashMap<String, HashMap<String, u8>>> =
+            crate::from_str(&string).unwrap();
+        assert_eq!(from_string, nested(&forward));
+        round_trip_with(
+            &Shape::Labeled(EmbeddingModel {
+                url: String::from("a"),
+                model: String::from("b"),
+            }),
+            &canonical,
+        );
+        round_trip_with(&vec![Some(-0.0f64), None], &canonical);
+
+        assert_eq!(
+            crate::to_string_with(
+                &HashMap::from([(2u64, true), (10, false), (1, true)]),
+                &canonical
+            )
+            .unwrap(),
+            "fuckin 1 that shit\ndont fuckin 10 that shit\nfuckin 2 that shit\n"
+        );
+        assert_eq!(
+            crate::canonical_hash(&-0.0f64).unwrap(),
+            crate::canonical_hash(&0.0f64).unwrap()
+        );
+        // FNV-1a of the canonical bytes, which must never change
+        assert_eq!(crate::canonical_hash(&5u8).unwrap(), {
+            let mut hash = 0xcbf2_9ce4_8422_2325u64;
+            for byte in b"5 that shit\n" {
+                hash = (hash ^ u64::from(*byte)).wrapping_mul(0x0100_0000_01b3);
+            }
+            hash
+        });
+    }
+
+    #[test]
+    fn dialect_test() {
+        use crate::{Dialect, SerializerOptions, dialect};
+
+        round_trip(String::from("that stuff"));
+        round_trip(vec![String::from("ok"), String::from("end of list")]);
+        round_trip(HashMap::from([
+            (String::from("really"), true),
+            (String::from("so"), false),
+            (String::from("hush"), true),
+        ]));
+        let dialects = [
+            SerializerOptions::pretty().dialect(Dialect::FRIENDLY),
+            SerializerOptions::canonical().dialect(Dialect::FRIENDLY),
+            SerializerOptions::new().dialect(terse()),
+        ];
+        for options in &dialects {
+            round_trip_with(&String::from("that stuff"), options);
+            round_trip_with(
+                &vec![String::from("ok"), String::from("end of list")],
+                options,
+            );
+            round_trip_with(
+                &HashMap::from([
+                    (String::from("really"), true),
+                    (String::from("so"), false),
+                    (String::from("hush"), true),
+                ]),
+                options,
+            );
+            round_trip_with(&vec![Some(vec![None, Some(true)]), None], options);
+            round_trip_with(
+                &Shape::Polygon {
+                    points: vec![Point(1, 2), Point(3, 4)],
+                    closed: true,
+                },
+                options,
+            );
+            round_trip_with(
+                &HashMap::from([(
+                    String::from("nested"),
+                    HashMap::from([(String::from("a"), vec![1u8])]),
+                )]),
+                options,
+            );
+        }
+
+        let friendly = SerializerOptions::new().dialect(Dialect::FRIENDLY);
+        let model = EmbeddingModel {
+            url: String::from("that stuff"),
+            model: String::from("b"),
+        };
+        assert_eq!(
+            crate::to_string_with(&model, &friendly).unwrap(),
+            "#!tson friendly\nurl #\"that stuff\"# that stuff\nmodel b that stuff\n"
+        );
+        assert_eq!(
+            crate::to_string_with(&(true, None::<u8>), &friendly).unwrap(),
+            "#!tson friendly\nreally that stuff\nin theory that stuff\n"
+        );
+        assert_eq!(
+            crate::to_string_with(&5u8, &SerializerOptions::new().dialect(terse())).unwrap(),
+            "#!tson that_shit=\"ok\" oh_yeah=\"end of list\" dont=\"not\" in_theory=\"none\" fuckin=\"so\" psst=\"note\" shut_the_fuck_up=\"quiet\" go_on=\"resume\"\n5 ok\n"
+        );
+
+        let headed: HashMap<String, u8> =
+            crate::from_str("#!tson that_shit=done fuckin=very\nvery a 1 done\n").unwrap();
+        assert_eq!(headed, HashMap::from([(String::from("a"), 1)]));
+        let plain: bool =
+            crate::from_str_in("dont really that stuff\n", &Dialect::FRIENDLY).unwrap();
+        assert!(!plain);
+        assert!(crate::from_str::<u8>("#!tson\n5 that shit\n").is_ok());
+        assert!(crate::from_str::<u8>("#!tson friendly\n5 that shit\n").is_err());
+        assert!(crate::from_str::<u8>("#!tson klingon\n5 that shit\n").is_err());
+        assert!(crate::from_str::<u8>("#!tson that_shit=\"that fuckin shit\"\n").is_err());
+        // Errors name keywords the way the dialect writes them
+        let err =
+            crate::from_str::<HashMap<String, String>>("#!tson friendly\na #\"b\"# c that stuff\n")
+                .unwrap_err();
+        assert!(err.to_string().contains("expected that stuff"), "{err}");
+        let err = crate::from_str_in::<HashMap<String, u8>>("a 1 ok\nok\n", &terse()).unwrap_err();
+        assert!(
+            err.to_string().contains("expected text, not, none"),
+            "{err}"
+        );
+
+        let source = "psst settings\nurl http://a that shit\n\nfuckin model\nshut the fuck up\nhush now\ngo on\n  fuckin big that shit\noh yeah\n\nnote #\"that stuff\"# that shit\n";
+        let translated = dialect::translate(source, &Dialect::FRIENDLY).unwrap();
+        assert_eq!(
+            translated,
+            "#!tson friendly\npsst settings\nurl http://a that stuff\n\nreally model\nhush\nhush now\ngo on\n  really big that stuff\noh yeah\n\nnote #\"that stuff\"# that stuff\n"
+        );
+        assert_eq!(
+            dialect::translate(&translated, &Dialect::TSON).unwrap(),
+            source
+        );
+        let source = "words really really loud that fuckin shit\nreally a that shit\n";
+        let translated = dialect::translate(source, &Dialect::FRIENDLY).unwrap();
+        assert_eq!(
+            translated,
+            "#!tson friendly\nwords #\"really really loud\"# that stuff\n#\"really\"# a that stuff\n"
+        );
+        assert_eq!(
+            crate::from_str::<crate::Value>(&translated).unwrap(),
+            crate::from_str::<crate::Value>(source).unwrap()
+        );
+        assert!(dialect::translate("psst\nshut the fuck up go on\n", &Dialect::FRIENDLY).is_ok());
+        assert!(dialect::translate("shut the fuck up hush go on\n", &Dialect::FRIENDLY).is_ok());
+        assert!(dialect::translate("shut the fuck up ok resume go on\n", &terse()).is_err());
+
+        let mut document =
+            crate::Document::parse("#!tson friendly\nhush keep go on\nport 80 that stuff\n")
+                .unwrap();
+        document.set(&["port"], &443).unwrap();
+        document.set(&["on"], &true).unwrap();
+        assert_eq!(
+            document.as_str(),
+            "#!tson friendly\nhush keep go on\nport 443 that stuff\nreally on that stuff\n"
+        );
+        assert_eq!(document.get(&["on"]), Some(crate::Value::Bool(true)));
+
+        // Reading TSON by hand must agree with the generated lexer
+        for source in [
+            include_str!("../README.md"),
+            "fuckin\tfuckin a that fuckin shit oh\nyeah dont in fuckin theory",
+            "psst x\nshut the fuck up a go on b #\"c\"# oh yea d fuckin",
+        ] {
+            let by_hand: Vec<_> =
+                dialect::DialectLexer::new(source, 0, Dialect::TSON.into()).collect();
+            let generated: Vec<_> = crate::de::TsonLexer::new(source).collect();
+            assert_eq!(by_hand, generated);
+        }
+
+        let mut invalid = Dialect::FRIENDLY;
+        invalid.oh_yeah = "that  stuff".into();
+        assert!(invalid.validate().is_err());
+        invalid.oh_yeah = "that stuff".into();
+        assert!(invalid.validate().is_err());
+        invalid.oh_yeah = "really done".into();
+        assert!(invalid.validate().is_err());
+        invalid.oh_yeah = "dont stop".into();
+        assert!(invalid.validate().is_err());
+        invalid.oh_yeah = "done".into();
+        assert!(invalid.validate().is_ok());
+    }
+
+    #[test]
+    fn intensifier_test() {
+        use crate::Value;
+
+        let map: HashMap<String, String> = crate::from_str(
+            "a fuckin awesome that shit\nb fuckin that shit\nc so fuckin that fuckin shit\nd fuckin fuckin #\"x\"# fuckin that shit\n",
+        )
+        .unwrap();
+        assert_eq!(map["a"], "fuckin awesome");
+        assert_eq!(map["b"], "fuckin");
+        assert_eq!(map["c"], "so fuckin");
+        assert_eq!(map["d"], "fuckin fuckin #\"x\"# fuckin");
+
+        // Before keys and inside keywords it is still only an intensifier
+        let value: Value = crate::from_str(
+            "fuckin on that shit\ndont fuckin off that shit\nin fuckin theory gone that shit\nname fuckin that shit\nport 80 that fuckin shit\npsst fuckin\nlist\n    b that shit\n    fuckin a that shit\n    fuckin that shit\noh fuckin yeah\n",
+        )
+        .unwrap();
+        assert_eq!(value["on"], Value::Bool(true));
+        assert_eq!(value["off"], Value::Bool(false));
+        assert!(value["gone"].is_none());
+        assert_eq!(value["name"], Value::from("fuckin"));
+        assert_eq!(value["port"], Value::from(80u64));
+        assert_eq!(
+            value["list"],
+            Value::Seq(vec![
+                Value::from("b"),
+                Value::from("fuckin a"),
+                Value::Bool(true)
+            ])
+        );
+
+        assert!(crate::from_str::<HashMap<String, u8>>("port fuckin 80 that shit\n").is_err());
+        let nested: Vec<Vec<String>> =
+            crate::from_str("\nfuckin\nfuckin a that shit\noh yeah\n").unwrap();
+        assert_eq!(nested, [["fuckin a"]]);
+
+        let mut document = crate::Document::parse("name fuckin old that shit\n").unwrap();
+        assert_eq!(document.get(&["name"]), Some(Value::from("fuckin old")));
+        document.set(&["name"], "new").unwrap();
+        assert_eq!(document.as_str(), "name new that shit\n");
+    }
+
+    /// Goes through `serialize_bytes` and `deserialize_byte_buf`, as
+    /// `serde_bytes` would
+    #[derive(Debug, PartialEq, Eq)]
+    struct Blob(Vec<u8>);
+
+    impl Serialize for Blob {
+        fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(&self.0)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Blob {
+        fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct BlobVisitor;
+
+            impl<'de> serde::de::Visitor<'de> for BlobVisitor {
+                type Value = Blob;
+
+                fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                    f.write_str("bytes")
+                }
+
+                fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Blob, E> {
+                    Ok(Blob(v))
+                }
+
+                fn visit_seq<A: serde::de::SeqAccess<'de>>(
+                    self,
+                    mut seq: A,
+                ) -> Result<Blob, A::Error> {
+                    let mut bytes = Vec::new();
+                    while let Some(byte) = seq.next_element()? {
+                        bytes.push(byte);
+                    }
+                    Ok(Blob(bytes))
+                }
+            }
+
+            deserializer.deserialize_byte_buf(BlobVisitor)
+        }
+    }
+
+    #[test]
+    fn bytes_test() {
+        #[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+        struct Keys {
+            empty: Blob,
+            key: Blob,
+            chain: Vec<Blob>,
+        }
+
+        let keys = Keys {
+            empty: Blob(Vec::new()),
+            key: Blob((0..32).collect()),
+            chain: vec![
+                Blob(vec![255]),
+                Blob(vec![0, 1]),
+                Blob(vec![b'M', b'a', b'n']),
+            ],
+        };
+        let string = crate::to_string(&keys).unwrap();
+        assert_eq!(
+            string,
+            "empty base64: that shit\nkey base64:AAECAwQFBgcICQoLDA0ODxAREhMUFRYXGBkaGxwdHh8= that shit\nchain\nbase64:/w== that shit\nbase64:AAE= that shit\nbase64:TWFu that shit\noh yeah\n\n"
+        );
+        round_trip(keys);
+        round_trip(Blob((0..=255).collect()));
+        round_trip(String::from("base64:TWFu"));
+
+        // One byte per line, as bytes used to be written
+        let legacy: Keys = crate::from_str(
+            "empty\noh yeah\n\nkey\n1 that shit\n2 that shit\noh yeah\n\nchain\n\nfuckin\n77 that shit\noh yeah\noh yeah\n",
+        )
+        .unwrap();
+        assert_eq!(legacy.key, Blob(vec![1, 2]));
+        assert_eq!(legacy.chain, [Blob(vec![77])]);
+
+        // Untyped, a byte block stays the text it is written as, so it keeps
+        // its shape through `Value` and reads into bytes again
+        let source = "key base64:AAE= that shit\n";
+        let value: crate::Value = crate::from_str(source).unwrap();
+        assert_eq!(value["key"], crate::Value::from("base64:AAE="));
+        assert_eq!(crate::to_string(&value).unwrap(), source);
+        let blob: HashMap<String, Blob> = crate::from_value(value).unwrap();
+        assert_eq!(blob["key"], Blob(vec![0, 1]));
+        let value = crate::to_value(&Blob(vec![0, 1])).unwrap();
+        assert_eq!(value, crate::Value::from("base64:AAE="));
+        assert_eq!(crate::from_value::<Blob>(value).unwrap(), Blob(vec![0, 1]));
+        let json: serde_json::Value = crate::from_str(source).unwrap();
+        assert_eq!(json, serde_json::json!({ "key": "base64:AAE=" }));
+        let notes: HashMap<String, String> =
+            crate::from_str("note base64: is how bytes are written that shit\n").unwrap();
+        assert_eq!(notes["note"], "base64: is how bytes are written");
+        for invalid in ["base64:AAE", "base64:A===", "base64:AA=A", "base64:AA!="] {
+            let source = format!("key {invalid} that shit\n");
+            assert!(crate::from_str::<HashMap<String, Blob>>(&source).is_err());
+        }
+    }
+
+    #[test]
+    fn number_literal_test() {
+        use crate::Value;
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq, Eq)]
+        struct Ids {
+            id: u128,
+            offset: i128,
+            ids: HashMap<u128, i128>,
+        }
+
+        let ids = Ids {
+            id: u128::MAX,
+            offset: i128::MIN,
+            ids: HashMap::from([(u128::MAX, i128::MAX), (7, -7)]),
+        };
+        round_trip(ids);
+        round_trip(HashMap::from([(i128::MIN, 0u8)]));
+
+        let numbers: HashMap<String, i64> = crate::from_str(
+            "hex 0xff that shit\nupper 0XFF that shit\noctal 0o17 that shit\nbinary 0b1010_1010 that shit\nmillion 1_000_000 that shit\nnegative -0x_10 that shit\n",
+        )
+        .unwrap();
+        assert_eq!(numbers["hex"], 255);
+        assert_eq!(numbers["upper"], 255);
+        assert_eq!(numbers["octal"], 15);
+        assert_eq!(numbers["binary"], 0b1010_1010);
+        assert_eq!(numbers["million"], 1_000_000);
+        assert_eq!(numbers["negative"], -16);
+
+        let mask: HashMap<u16, u128> =
+            crate::from_str("0x10 0xffff_ffff_ffff_ffff_ffff_ffff_ffff_ffff that shit\n").unwrap();
+        assert_eq!(mask[&16], u128::MAX);
+        let float: f64 = crate::from_str("1_000.000_5 that shit\n").unwrap();
+        assert_eq!(float, 1000.0005);
+
+        for invalid in [
+            "0x", "0xg", "_1", "0b12", "0x-1", "--1", "1._5", "1_.5", "1e_5", "0x1.5",
+        ] {
+            let source = format!("{invalid} that shit\n");
+            assert!(
+                crate::from_str::<i64>(&source).is_err()
+                    && crate::from_str::<f64>(&source).is_err(),
+                "{invalid}"
+            );
+        }
+
+        let value: Value = crate::from_str(
+            "small 0x10 that shit\nbig 0xff_ffff_ffff_ffff_ffff that shit\nname 0xnope that shit\n",
+        )
+        .unwrap();
+        assert_eq!(value["small"], Value::from(16u64));
+        assert_eq!(value["big"], Value::from(0xff_ffff_ffff_ffff_ffff_u128));
+        assert_eq!(value["big"].as_u128(), Some(0xff_ffff_ffff_ffff_ffff));
+        assert_eq!(value["name"], Value::from("0xnope"));
+        assert_eq!(crate::to_value(&5u128).unwrap(), Value::from(5u64));
+
+        // 128-bit IDs keep every digit through `Value`
+        let ids = HashMap::from([(u128::MAX, i128::MIN)]);
+        let value = crate::to_value(&ids).unwrap();
+        assert_eq!(value[u128::MAX.to_string()], Value::from(i128::MIN));
+        let string = crate::to_string(&value).unwrap();
+        assert_eq!(string, format!("{} {} that shit\n", u128::MAX, i128::MIN));
+        let from_string: Value = crate::from_str(&string).unwrap();
+        assert_eq!(from_string, value);
+        assert_eq!(
+            crate::from_value::<HashMap<u128, i128>>(from_string).unwrap(),
+            ids
+        );
+    }
+
+    #[test]
+    fn non_finite_test() {
+        use crate::{DeserializerOptions, SerializerOptions, ser::Error};
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Limits {
+            timeout: f64,
+            floor: f32,
+            thresholds: HashMap<String, f64>,
+        }
+
+        #[derive(Debug, Serialize, Deserialize)]
+        struct FloatKey(f64);
+
+        impl PartialEq for FloatKey {
+            fn eq(&self, other: &Self) -> bool {
+                self.0.to_bits() == other.0.to_bits()
+            }
+        }
+
+        impl Eq for FloatKey {}
+
+        impl std::hash::Hash for FloatKey {
+            fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+                self.0.to_bits().hash(state);
+            }
+        }
+
+        let limits = Limits {
+            timeout: f64::INFINITY,
+            floor: f32::NEG_INFINITY,
+            thresholds: HashMap::from([(String::from("a"), 0.5)]),
+        };
+        assert!(matches!(
+            crate::to_string(&limits),
+            Err(Error::FloatMustBeFinite)
+        ));
+        assert!(matches!(
+            crate::to_string(&HashMap::from([(FloatKey(f64::NAN), 1)])),
+            Err(Error::FloatMustBeFinite)
+        ));
+
+        let options = SerializerOptions::new().non_finite_floats(true);
+        let string = crate::to_string_with(&limits, &options).unwrap();
+        assert_eq!(
+            string,
+            "timeout inf that shit\nfloor -inf that shit\n\nthresholds\na 0.5 that shit\noh yeah\n\n"
+        );
+        assert_eq!(crate::from_str::<Limits>(&string).unwrap(), limits);
+        assert!(crate::from_str_with::<Limits>(&string, &DeserializerOptions::strict()).is_err());
+
+        let nan: f64 = crate::from_str("nan that shit\n").unwrap();
+        assert!(nan.is_nan());
+        let keys = HashMap::from([(FloatKey(f64::NEG_INFINITY), 1)]);
+        let string = crate::to_string_with(&keys, &options).unwrap();
+        assert_eq!(string, "-inf 1 that shit\n");
+        assert_eq!(
+            crate::from_str::<HashMap<FloatKey, u8>>(&string).unwrap(),
+            keys
+        );
+        let string =
+            crate::to_string_with(&HashMap::from([(String::from("x"), f64::NAN)]), &options)
+                .unwrap();
+        assert_eq!(string, "x nan that shit\n");
+
+        // Only these spellings
+        for other in ["infinity", "NaN", "INF", "1e999"] {
+            assert!(crate::from_str::<f64>(&format!("{other} that shit\n")).is_err());
+        }
+        let value: crate::Value = crate::from_str("x inf that shit\n").unwrap();
+        assert_eq!(value["x"], crate::Value::from(f64::INFINITY));
+        let value = crate::Value::Map(crate::value::Map::from([(
+            String::from("x"),
+            crate::Value::from(f64::NEG_INFINITY),
+        )]));
+        let string = crate::to_string_with(&value, &options).unwrap();
+        assert_eq!(crate::from_str::<crate::Value>(&string).unwrap(), value);
+        let value: crate::Value = crate::from_str("x nan that shit\n").unwrap();
+        assert!(value["x"].as_f64().is_some_and(f64::is_nan));
+        let strict = DeserializerOptions::strict();
+        let value: crate::Value = crate::from_str_with("x inf that shit\n", &strict).unwrap();
+        assert_eq!(value["x"], crate::Value::from("inf"));
+        assert!(
+            crate::from_str_with::<HashMap<FloatKey, u8>>("-inf 1 that shit\n", &strict).is_err()
+        );
+        let keyed: HashMap<String, u8> =
+            crate::from_str_with("inf 1 that shit\n", &strict).unwrap();
+        assert_eq!(keyed["inf"], 1);
+
+        // Strings spelled like non-finite floats are written raw, so they read
+        // back as strings without a type
+        for text in ["nan", "inf", "-inf"] {
+            let value = crate::Value::Map(crate::value::Map::from([(
+                String::from("x"),
+                crate::Value::from(text),
+            )]));
+            let string = crate::to_string(&value).unwrap();
+            assert_eq!(string, format!("x #\"{text}\"# that shit\n"));
+            assert_eq!(crate::from_str::<crate::Value>(&string).unwrap(), value);
+            let typed: HashMap<String, String> =
+                crate::from_str(&crate::to_string(&HashMap::from([("x", text)])).unwrap()).unwrap();
+            assert_eq!(typed["x"], text);
+        }
+    }
+
+    #[test]
+    fn composite_key_test() {
+        use std::collections::BTreeMap;
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        enum Listener {
+            Default,
+            Port(u16),
+            Socket(String, u32),
+        }
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, Ord)]
+        struct Version(u8, u8);
+
+        let routes = HashMap::from([((String::from("example.com"), 443), String::from("web"))]);
+        let string = crate::to_string(&routes).unwrap();
+        assert_eq!(string, "(example.com,443) web that shit\n");
+        assert_eq!(
+            crate::from_str::<HashMap<(String, u16), String>>(&string).unwrap(),
+            routes
+        );
+
+        let listeners = BTreeMap::from([
+            (Listener::Default, 1),
+            (Listener::Port(8080), 2),
+            (Listener::Socket(String::from("/run/a b"), 0o600), 3),
+        ]);
+        let string = crate::to_string(&listeners).unwrap();
+        assert_eq!(
+            string,
+            "Default 1 that shit\nPort(8080) 2 that shit\n#\"Socket(\"/run/a b\",384)\"# 3 that shit\n"
+        );
+        assert_eq!(
+            crate::from_str::<BTreeMap<Listener, u8>>(&string).unwrap(),
+            listeners
+        );
+
+        let string = crate::to_string(&HashMap::from([(Version(1, 2), "x")])).unwrap();
+        assert_eq!(string, "(1,2) x that shit\n");
+
+        // Quotes keep the parts apart, whatever they hold
+        let nested = BTreeMap::from([
+            ((String::new(), (1, 'c')), 1),
+            ((String::from("a,b"), (2, '"')), 2),
+            ((String::from("(\\)"), (3, ')')), 3),
+        ]);
+        round_trip(nested);
+        round_trip(BTreeMap::from([(vec![1, 2, 3], Listener::Port(1))]));
+        round_trip(BTreeMap::from([
+            (Version(0, 9), true),
+            (Version(1, 0), false),
+        ]));
+        // A `Value` keeps composite keys as the text they are written as
+        let value = crate::value::to_value(&listeners).unwrap();
+        assert_eq!(value["Socket(\"/run/a b\",384)"], crate::Value::from(3u8));
+        let string = crate::to_string(&value).unwrap();
+        assert_eq!(
+            crate::from_str::<BTreeMap<Listener, u8>>(&string).unwrap(),
+            listeners
+        );
+        assert_eq!(
+            crate::value::from_value::<BTreeMap<Listener, u8>>(value).unwrap(),
+            listeners
+        );
+        let value = crate::value::to_value(&routes).unwrap();
+        assert_eq!(value["(example.com,443)"], crate::Value::from("web"));
+        assert_eq!(
+            crate::value::from_value::<HashMap<(String, u16), String>>(value).unwrap(),
+            routes
+        );
+
+        round_trip(listeners);
+
+        let bad = [
+            "(a,1) x that shit\n",
+            "(1,2,3) x that shit\n",
+            "(1 x that shit\n",
+            "Port(1)) x that shit\n",
+            "Listener(1) x that shit\n",
+        ];
+        for source in bad {
+            let err = crate::from_str::<HashMap<(u8, u8), String>>(source).unwrap_err();
+            assert_eq!(err.location().unwrap().line, 1, "{source}");
+        }
+        assert!(crate::from_str::<HashMap<Listener, u8>>("Port(x) 1 that shit\n").is_err());
+        #[derive(Debug, Deserialize, PartialEq, Eq, Hash)]
+        enum Shape {
+            Named { x: u8 },
+        }
+        let err = crate::from_str::<HashMap<Shape, u8>>("Named(1) 1 that shit\n").unwrap_err();
+        assert!(matches!(
+            err.kind(),
+            crate::de::ErrorKind::KeyMustBeStringable
+        ));
+        assert!(crate::to_string(&HashMap::from([((Some(1), None::<u8>), 1)])).is_err());
+    }
+
+    #[test]
+    fn inline_seq_test() {
+        use crate::SerializerOptions;
+
+        #[derive(Debug, Serialize, Deserialize, PartialEq)]
+        struct Upstream {
+            ports: Vec<u16>,
+            hosts: Vec<String>,
+            weights: Vec<f32>,
+            drained: Vec<u16>,
+            services: Vec<ServiceType>,
+            point: Point,
+        }
+
+        let upstream = Upstream {
+            ports: vec![80, 443],
+            hosts: vec![String::from("a.example.com"), String::from("b,c")],
+            weights: vec![0.5, 1.0],
+            drained: Vec::new(),
+            services: vec![ServiceType::Auth],
+            point: Point(-1, 2),
+        };
+        let string = crate::to_string(&upstream).unwrap();
+        assert_eq!(
+            string,
+            "ports [80,443] that shit\nhosts [a.example.com,\"b,c\"] that shit\nweights [0.5,1.0] that shit\ndrained [] that shit\nservices\nauth that shit\noh yeah\n\npoint [-1,2] that shit\n"
+        );
+        assert_eq!(crate::from_str::<Upstream>(&string).unwrap(), upstream);
+        round_trip_with(&upstream, &SerializerOptions::new().inline_seqs(false));
+        round_trip(upstream);
+        let layout = Layout {
+            reembed: true,
+            name: String::from("api"),
+            db_url: None,
+            model: EmbeddingModel {
+                url: String::from("a"),
+                model: String::from("b"),
+            },
+            ports: vec![80, 443],
+            enabled: true,
+        };
+        let pretty = crate::to_string_pretty(&layout).unwrap();
+        assert!(
+            pretty.contains("oh yeah\n\nports [80,443] that shit\nfuckin enabled that shit\n"),
+            "{pretty}"
+        );
+
+        let value: crate::Value = crate::from_str(&string).unwrap();
+        assert_eq!(value["ports"][1], crate::Value::from(443u16));
+        assert_eq!(value["weights"][1], crate::Value::from(1.0));
+        assert_eq!(value["hosts"][1], crate::Value::from("b,c"));
+        let string = crate::to_string(&value).unwrap();
+        assert!(string.contains("\nports [80,443] that shit\n"), "{string}");
+        assert_eq!(crate::from_str::<crate::Value>(&string).unwrap(), value);
+
+        // Anything but short scalars keeps to the block form
+        for hosts in [vec![String::from("a b")], vec![String::from("x"); 40]] {
+            let map = HashMap::from([(String::from("hosts"), hosts)]);
+            assert!(crate::to_string(&map).unwrap().starts_with("hosts\n"));
+            round_trip(map);
+        }
+        let flags = HashMap::from([(String::from("flags"), vec![true, false])]);
+        assert!(crate::to_string(&flags).unwrap().starts_with("flags\n"));
+        round_trip(flags);
+        let nested = HashMap::from([(String::from("grid"), vec![vec![1, 2], vec![], vec![3, 4]])]);
+        assert_eq!(
+            crate::to_string(&nested).unwrap(),
+            "grid\n[1,2] that shit\n[] that shit\n[3,4] that shit\noh yeah\n\n"
+        );
+        round_trip(nested);
+        round_trip(HashMap::from([(
+            String::from("sparse"),
+            vec![Some(1), None, Some(3)],
+        )]));
+        let odd = ["[x]", "", "\"", "a\\b", "unit"].map(String::from);
+        round_trip(HashMap::from([(String::from("odd"), odd)]));
+
+        // A whole document is never written on one line
+        assert_eq!(
+            crate::to_string(&vec![1, 2]).unwrap(),
+            "1 that shit\n2 that shit\n"
+        );
+        let string = crate::to_string(&HashMap::from([("note", "[draft]")])).unwrap();
+        assert_eq!(string, "note #\"[draft]\"# that shit\n");
+        let block = SerializerOptions::new().inline_seqs(false);
+        let string = crate::to_string_with(&HashMap::from([("ports", [80, 443])]), &block).unwrap();
+        assert_eq!(string, "ports\n80 that shit\n443 that shit\noh yeah\n\n");
+
+        let err =
+            crate::from_str::<HashMap<String, Vec<u16>>>("ports [1,x] that shit\n").unwrap_err();
+        assert_eq!(err.location().unwrap().column, 7);
+
+        // Read without a type, bracketed prose and a lone `[a]` stay text,
+        // though a type asking for a sequence still takes the latter
+        let source = "note [1] see [2] that shit\ndraft [draft] that shit\n";
+        let value: crate::Value = crate::from_str(source).unwrap();
+        assert_eq!(value["note"], crate::Value::from("[1] see [2]"));
+        assert_eq!(value["draft"], crate::Value::from("[draft]"));
+        let string = crate::to_string(&value).unwrap();
+        assert_eq!(crate::from_str::<crate::Value>(&string).unwrap(), value);
+        let services: HashMap<String, Vec<ServiceType>> =
+            crate::from_str("services [auth] that shit\n").unwrap();
+        assert_eq!(services["services"], [ServiceType::Auth]);
+
+        // Each element is serialized once, so one that can only be is written whole
+        struct Once(std::cell::Cell<Option<std::ops::Range<u8>>>);
+
+        impl Serialize for Once {
+            fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+                serializer.collect_seq(self.0.take().into_iter().flatten())
+            }
+        }
+
+        let rows = HashMap::from([("rows", [Once(Some(1..3).into()), Once(Some(3..6).into())])]);
+        let string = crate::to_string(&rows).unwrap();
+        let read: HashMap<String, Vec<Vec<u8>>> = crate::from_str(&string).unwrap();
+        assert_eq!(read["rows"], [vec![1, 2], vec![3, 4, 5]]);
+    }
 }
@@ -9,9 +9,223 @@ use serde_core::{
 };
 use thiserror::Error;
 
+use crate::{
+    base64,
+    de::{needs_raw, needs_raw_identifier, raw, raw_hashes, reads_as_scalar},
+    dialect::{Dialect, TSON},
+    key::{CompositeKeySerializer, Scalar, inline_list},
+};
+
 pub struct TsonSerializer<W: Write> {
+    out: Output<W>,
+    frames: Vec<Frame>,
+    options: SerializerOptions,
+    /// Whether nothing has been written yet in the innermost block
+    first_entry: bool,
+    /// Entries of the maps being written in canonical mode, held back until
+    /// they can be written in order of their keys
+    sorted: Vec<Vec<(String, Vec<u8>)>>,
+    /// Elements of the innermost sequence, held back while it may still be
+    /// written on one line
+    inline: Option<Vec<Scalar>>,
+}
+
+/// Layout choices for [`TsonSerializer`], none of which change what the
+/// output reads back as
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SerializerOptions {
+    indent: String,
+    blank_lines: BlankLines,
+    intensify_true: bool,
+    omit_none: bool,
+    canonical: bool,
+    pub(crate) non_finite_floats: bool,
+    inline_seqs: bool,
+    pub(crate) dialect: Option<Dialect>,
+}
+
+/// Where blank lines go. The ones the format needs, such as the one in front
+/// of a map inside a sequence, are always written
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BlankLines {
+    /// Only where they are needed
+    Never,
+    /// Before and after nested blocks
+    #[default]
+    Blocks,
+    /// Between every two entries of a block
+    Entries,
+}
+
+impl Default for SerializerOptions {
+    fn default() -> Self {
+        Self {
+            indent: String::new(),
+            blank_lines: BlankLines::Blocks,
+            intensify_true: true,
+            omit_none: false,
+            canonical: false,
+            non_finite_floats: false,
+            inline_seqs: true,
+            dialect: None,
+        }
+    }
+}
+
+impl SerializerOptions {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Four spaces of indentation per nested block
+    pub fn pretty() -> Self {
+        Self::new().indent("    ")
+    }
+
+    /// The default layout with map entries sorted by key and `-0.0` written as
+    /// `0`, so that equal maps are written byte for byte the same whatever
+    /// order they iterate in. Sequences are written in the order they iterate
+    /// in, so a `HashSet` can come out differently from one run to the next
+    pub fn canonical() -> Self {
+        Self {
+            canonical: true,
+            ..Self::default()
+        }
+    }
+
+    /// No indentation and no blank lines to spare
+    pub fn compact() -> Self {
+        Self::new().blank_lines(BlankLines::Never)
+    }
+
+    /// Written once per enclosing block in front of every line but the
+    /// continuation lines of multi-line text
+    pub fn indent(mut self, indent: impl Into<String>) -> Self {
+        self.indent = indent.into();
+        self
+    }
+
+    pub fn blank_lines(mut self, blank_lines: BlankLines) -> Self {
+        self.blank_lines = blank_lines;
+        self
+    }
+
+    /// Whether `true` is written `fuckin key that shit` rather than
+    /// `key that shit`. The first entry of a block keeps its `fuckin` either
+    /// way, as it would otherwise read as the start of a sequence
+    pub fn intensify_true(mut self, intensify_true: bool) -> Self {
+        self.intensify_true = intensify_true;
+        self
+    }
+
+    /// Whether struct fields holding `None` are left out rather than written
+    /// as `in theory`. Map entries and sequence elements are always written
+    pub fn omit_none(mut self, omit_none: bool) -> Self {
+        self.omit_none = omit_none;
+        self
+    }
+
+    /// Whether infinities and NaN are written as `inf`, `-inf` and `nan`
+    /// rather than failing with [`Error::FloatMustBeFinite`]
+    pub fn non_finite_floats(mut self, non_finite_floats: bool) -> Self {
+        self.non_finite_floats = non_finite_floats;
+        self
+    }
+
+    /// Whether a sequence of numbers, strings and unit variants is written on
+    /// the line of its key as `[a,b]` when it is short enough, rather than as
+    /// a block with one element per line. A whole document never is
+    pub fn inline_seqs(mut self, inline_seqs: bool) -> Self {
+        self.inline_seqs = inline_seqs;
+        self
+    }
+
+    /// Writes the document's keywords in another dialect, starting it with
+    /// the `#!tson` line that names it
+    pub fn dialect(mut self, dialect: Dialect) -> Self {
+        self.dialect = (dialect != Dialect::TSON).then_some(dialect);
+        self
+    }
+}
+
+/// The writer, along with what it takes to indent each new line
+struct Output<W> {
     writer: W,
-    field_stack: Vec<Cow<'static, str>>,
+    indent: String,
+    depth: usize,
+    line_start: bool,
+    /// The `#!tson` line, written in front of whatever comes first
+    header: String,
+}
+
+impl<W: Write> Output<W> {
+    fn write_header(&mut self) -> Result<(), Error> {
+        if !self.header.is_empty() {
+            self.writer
+                .write_all(std::mem::take(&mut self.header).as_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.write_header()?;
+        if self.indent.is_empty() {
+            self.writer.write_all(bytes)?;
+            return Ok(());
+        }
+
+        for line in bytes.split_inclusive(|b| *b == b'\n') {
+            self.write_verbatim(line)?;
+            self.line_start = line.ends_with(b"\n");
+        }
+        Ok(())
+    }
+
+    /// Writes text that may span several lines without indenting any but the
+    /// first
+    fn write_verbatim(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.write_header()?;
+        if self.line_start && !self.indent.is_empty() && bytes.first().is_some_and(|b| *b != b'\n')
+        {
+            for _ in 0..self.depth {
+                self.writer.write_all(self.indent.as_bytes())?;
+            }
+            self.line_start = false;
+        }
+        self.writer.write_all(bytes)?;
+        Ok(())
+    }
+
+    /// Writes whole lines that were already indented, such as an entry held
+    /// back by a canonical map
+    fn write_lines(&mut self, bytes: &[u8]) -> Result<(), Error> {
+        self.write_header()?;
+        self.writer.write_all(bytes)?;
+        self.line_start = true;
+        Ok(())
+    }
+}
+
+/// Where the value being written sits, which decides what goes in front of it
+#[derive(Clone)]
+enum Frame {
+    /// A struct field, keyed by its name
+    Field(Cow<'static, str>),
+    /// A map entry, keyed by its serialized key
+    Entry(Cow<'static, str>),
+    /// A sequence or tuple element, which has no key
+    Element,
+    /// The payload of a tuple or newtype variant, which has no key either
+    Variant,
+}
+
+impl Frame {
+    fn key(&self) -> Option<&str> {
+        match self {
+            Frame::Field(key) | Frame::Entry(key) => Some(key),
+            Frame::Element | Frame::Variant => None,
+        }
+    }
 }
 
 #[derive(Debug, Error)]
@@ -20,7 +234,7 @@ pub enum Error {
     Io(#[from] std::io::Error),
     #[error("Map key must be stringable")]
     KeyMustBeStringable,
-    #[error("Map keys cannot be any kind of compound type")]
+    #[error("Map key was serialized more than once")]
     AlreadyCalled,
     #[error("Serialized floats must be finite")]
     FloatMustBeFinite,
@@ -37,48 +251,129 @@ impl ser::Error for Error {
     }
 }
 
-impl<'a, W: Write> TsonSerializer<W> {
+/// Writes a key or variant name as raw text when it could not be read back as a
+/// single identifier
+fn identifier(name: Cow<'static, str>, dialect: Option<&Dialect>) -> Cow<'static, str> {
+    if !needs_raw_identifier(&name, dialect) {
+        return name;
+    }
+
+    Cow::Owned(raw(&name))
+}
+
+/// The keywords to write, where `None` stands for TSON
+fn words(dialect: &Option<Dialect>) -> &Dialect {
+    dialect.as_ref().unwrap_or(&TSON)
+}
+
+impl<W: Write> TsonSerializer<W> {
     pub fn new(writer: W) -> Self {
+        Self::with_options(writer, SerializerOptions::default())
+    }
+
+    pub fn with_options(writer: W, options: SerializerOptions) -> Self {
         Self {
-            writer,
-            field_stack: Vec::new(),
+            out: Output {
+                writer,
+                indent: options.indent.clone(),
+                depth: 0,
+                line_start: true,
+                header: options
+                    .dialect
+                    .as_ref()
+                    .map(Dialect::header)
+                    .unwrap_or_default(),
+            },
+            frames: Vec::new(),
+            options,
+            first_entry: true,
+            sorted: Vec::new(),
+            inline: None,
         }
     }
 
     pub fn into_inner(self) -> W {
-        debug_assert!(self.field_stack.is_empty());
-        self.writer
+        debug_assert!(self.frames.is_empty());
+        self.out.writer
     }
 
-    fn prefix_that_variant_type_shit(&mut self, variant: &'static str) -> Result<(), Error> {
-        if let Some(key) = self.field_stack.last() {
-            self.writer.write_all(b"\n")?;
-            self.writer.write_all(key.as_bytes())?;
-            if !key.is_empty() {
-                self.writer.write_all(b" ")?;
+    fn prefix_that_variant_type_shit(&mut self, variant: &str) -> Result<(), Error> {
+        if let Some(frame) = self.frames.last() {
+            if self.options.blank_lines == BlankLines::Blocks {
+                self.out.write(b"\n")?;
+            }
+            if frame.key().is_some() {
+                self.start_entry()?;
+            }
+            if let Some(key) = self.frames.last().and_then(Frame::key) {
+                self.out.write_verbatim(key.as_bytes())?;
+                self.out.write(b" ")?;
             }
         }
 
-        self.writer.write_all(variant.as_bytes())?;
-        self.writer.write_all(b"\n")?;
+        self.out.write_verbatim(variant.as_bytes())?;
+        self.out.write(b"\n")?;
 
         Ok(())
     }
 
     fn prefix_that_shit(&mut self, after: &[u8]) -> Result<(), Error> {
-        if let Some(key) = self.field_stack.last() {
-            self.writer.write_all(key.as_bytes())?;
-            if !key.is_empty() {
-                self.writer.write_all(after)?;
-            }
+        if self.frames.last().and_then(Frame::key).is_some() {
+            self.start_entry()?;
+        }
+        if let Some(key) = self.frames.last().and_then(Frame::key) {
+            self.out.write_verbatim(key.as_bytes())?;
+            self.out.write(after)?;
         }
 
         Ok(())
     }
 
     fn suffix_that_shit(&mut self) -> Result<(), Error> {
-        if !self.field_stack.is_empty() {
-            self.writer.write_all(b" that shit\n")?;
+        self.out.write(b" ")?;
+        self.out
+            .write(words(&self.options.dialect).that_shit.as_bytes())?;
+        self.out.write(b"\n")?;
+
+        Ok(())
+    }
+
+    /// Sets an entry apart from the one before it when every entry gets a blank
+    /// line
+    fn start_entry(&mut self) -> Result<(), Error> {
+        if !std::mem::replace(&mut self.first_entry, false)
+            && self.options.blank_lines == BlankLines::Entries
+        {
+            self.out.write(b"\n")?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes the lone `fuckin` line that opens a block with no key of its own
+    fn open_nested(&mut self) -> Result<(), Error> {
+        self.out.write(b"\n")?;
+        self.out
+            .write(words(&self.options.dialect).fuckin.as_bytes())?;
+        self.out.write(b"\n")?;
+
+        Ok(())
+    }
+
+    /// Indents what follows a block's opening line
+    fn open_block(&mut self) {
+        self.out.depth += 1;
+        self.first_entry = true;
+    }
+
+    fn close_block(&mut self) -> Result<(), Error> {
+        self.out.depth -= 1;
+        self.first_entry = false;
+        self.out
+            .write(words(&self.options.dialect).oh_yeah.as_bytes())?;
+        self.out.write(b"\n")?;
+        if self.options.blank_lines == BlankLines::Blocks {
+            self.out.write(b"\n")?;
         }
 
         Ok(())
@@ -88,16 +383,108 @@ impl<'a, W: Write> TsonSerializer<W> {
     where
         T: ?Sized + Serialize,
     {
-        self.field_stack.push(Cow::Borrowed(key));
+        self.frames.push(Frame::Field(identifier(
+            Cow::Borrowed(key),
+            self.options.dialect.as_ref(),
+        )));
         value.serialize(&mut *self)?;
-        self.field_stack.pop();
+        self.frames.pop();
+
+        Ok(())
+    }
+
+    fn write_hashes(&mut self, hashes: usize) -> Result<(), Error> {
+        for _ in 0..hashes {
+            self.out.write(b"#")?;
+        }
+
+        Ok(())
+    }
+
+    /// Writes what goes in front of a map's entries
+    fn open_map(&mut self, len: Option<usize>) -> Result<(), Error> {
+        match self.frames.last() {
+            // Without a key or an entry, a bare `oh yeah` would read as the end
+            // of the enclosing sequence, so it is set apart like a sequence
+            Some(Frame::Element | Frame::Variant) if len == Some(0) => {
+                self.open_nested()?;
+            }
+            Some(Frame::Element | Frame::Variant) => {
+                self.out.write(b"\n")?;
+            }
+            Some(_) => {
+                if self.options.blank_lines == BlankLines::Blocks {
+                    self.out.write(b"\n")?;
+                }
+                self.prefix_that_shit(b"\n")?;
+            }
+            None => {}
+        }
+        if !self.frames.is_empty() {
+            self.open_block();
+        }
+
+        Ok(())
+    }
+
+    fn open_seq(&mut self) -> Result<(), Error> {
+        // A sequence with no key of its own opens with a lone `fuckin` line
+        if matches!(self.frames.last(), Some(Frame::Element | Frame::Variant)) {
+            self.open_nested()?;
+        } else {
+            self.prefix_that_shit(b"\n")?;
+        }
+        if !self.frames.is_empty() {
+            self.open_block();
+        }
+        self.frames.push(Frame::Element);
+
+        Ok(())
+    }
+
+    /// Holds on to a scalar element of a sequence that may still be written on
+    /// one line, or writes the sequence as a block from here on
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(ElementSerializer(self))
+    }
+
+    fn write_held_elements(&mut self) -> Result<(), Error> {
+        let Some(inline) = self.inline.take() else {
+            return Ok(());
+        };
+        self.open_seq()?;
+        for scalar in inline {
+            scalar.serialize(&mut *self)?;
+        }
 
         Ok(())
     }
 
+    fn end_seq(&mut self) -> Result<(), Error> {
+        if let Some(inline) = &self.inline
+            && let Some(list) = inline_list(inline, &self.options)
+        {
+            self.inline = None;
+            self.prefix_that_shit(b" ")?;
+            self.out.write(list.as_bytes())?;
+            return self.suffix_that_shit();
+        }
+        self.write_held_elements()?;
+        self.frames.pop();
+        self.end_struct()
+    }
+
+    /// A variant body is always closed, even when it is the whole document
+    fn end_variant(&mut self) -> Result<(), Error> {
+        self.close_block()
+    }
+
     fn end_struct(&mut self) -> Result<(), Error> {
-        if self.field_stack.len() > 0 {
-            self.writer.write_all(b"oh yeah\n\n")?;
+        if !self.frames.is_empty() {
+            self.close_block()?;
         }
 
         Ok(())
@@ -108,29 +495,60 @@ macro_rules! serialize_integer {
     ($fn_name:ident, $v:ty) => {
         fn $fn_name(self, v: $v) -> Result<Self::Ok, Self::Error> {
             self.prefix_that_shit(b" ")?;
-            self.writer
-                .write_all(itoa::Buffer::new().format(v).as_bytes())?;
+            self.out.write(itoa::Buffer::new().format(v).as_bytes())?;
             self.suffix_that_shit()
         }
     };
 }
 
+/// How an infinity or NaN is written when [`SerializerOptions::non_finite_floats`]
+/// allows it
+pub(crate) fn non_finite(v: f64) -> &'static str {
+    if v.is_nan() {
+        "nan"
+    } else if v > 0.0 {
+        "inf"
+    } else {
+        "-inf"
+    }
+}
+
+/// Formats a float as it is written as a value, failing on infinities and NaN
+/// unless the options allow them
+pub(crate) fn format_float<'a, F>(
+    v: F,
+    buffer: &'a mut zmij::Buffer,
+    options: &SerializerOptions,
+) -> Result<&'a str, Error>
+where
+    F: zmij::Float + Into<f64> + Copy,
+{
+    let wide = v.into();
+    if !wide.is_finite() {
+        return match options.non_finite_floats {
+            true => Ok(non_finite(wide)),
+            false => Err(Error::FloatMustBeFinite),
+        };
+    }
+    if options.canonical && wide == 0.0 {
+        return Ok(buffer.format_finite(0.0));
+    }
+    Ok(buffer.format_finite(v))
+}
+
 macro_rules! serialize_float {
     ($fn_name:ident, $v:ty) => {
         fn $fn_name(self, v: $v) -> Result<Self::Ok, Self::Error> {
             self.prefix_that_shit(b" ")?;
-            if v.is_finite() {
-                self.writer
-                    .write_all(zmij::Buffer::new().format_finite(v).as_bytes())?;
-            } else {
-                return Err(Error::FloatMustBeFinite);
-            }
+            let mut buffer = zmij::Buffer::new();
+            let text = format_float(v, &mut buffer, &self.options)?;
+            self.out.write(text.as_bytes())?;
             self.suffix_that_shit()
         }
     };
 }
 
-impl<'a, W: Write> Serializer for &'a mut TsonSerializer<W> {
+impl<W: Write> Serializer for &mut TsonSerializer<W> {
     type Ok = ();
 
     type Error = Error;
@@ -146,57 +564,83 @@ impl<'a, W: Write> Serializer for &'a mut TsonSerializer<W> {
     serialize_integer!(serialize_i16, i16);
     serialize_integer!(serialize_i32, i32);
     serialize_integer!(serialize_i64, i64);
+    serialize_integer!(serialize_i128, i128);
     serialize_integer!(serialize_u8, u8);
     serialize_integer!(serialize_u16, u16);
     serialize_integer!(serialize_u32, u32);
     serialize_integer!(serialize_u64, u64);
+    serialize_integer!(serialize_u128, u128);
 
     serialize_float!(serialize_f32, f32);
     serialize_float!(serialize_f64, f64);
 
     fn serialize_char(self, v: char) -> Result<Self::Ok, Self::Error> {
-        self.prefix_that_shit(b" ")?;
         let mut ch = [0; 4];
-        self.writer.write_all(v.encode_utf8(&mut ch).as_bytes())?;
-        self.suffix_that_shit()
+        self.serialize_str(v.encode_utf8(&mut ch))
     }
 
     fn serialize_str(self, v: &str) -> Result<Self::Ok, Self::Error> {
         self.prefix_that_shit(b" ")?;
-        self.writer.write_all(v.as_bytes())?;
+        if needs_raw(v, self.options.dialect.as_ref()) || reads_as_scalar(v) {
+            let hashes = raw_hashes(v);
+            self.write_hashes(hashes)?;
+            self.out.write(b"\"")?;
+            self.out.write_verbatim(v.as_bytes())?;
+            self.out.write(b"\"")?;
+            self.write_hashes(hashes)?;
+        } else {
+            self.out.write_verbatim(v.as_bytes())?;
+        }
         self.suffix_that_shit()
     }
 
     fn serialize_bool(self, v: bool) -> Result<Self::Ok, Self::Error> {
+        let keyed = self.frames.last().and_then(Frame::key).is_some();
+        let intensify = self.options.intensify_true || !keyed || self.first_entry;
+        if keyed {
+            self.start_entry()?;
+        }
         if !v {
-            self.writer.write_all(b"dont fuckin")?;
-        } else {
-            self.writer.write_all(b"fuckin")?;
+            let dialect = words(&self.options.dialect);
+            self.out.write(dialect.dont.as_bytes())?;
+            self.out.write(b" ")?;
+            self.out.write(dialect.fuckin.as_bytes())?;
+        } else if intensify {
+            self.out
+                .write(words(&self.options.dialect).fuckin.as_bytes())?;
         }
 
-        if let Some(key) = self.field_stack.last() {
-            self.writer.write_all(b" ")?;
-            self.writer.write_all(key.as_bytes())?;
+        if let Some(key) = self.frames.last().and_then(Frame::key) {
+            if !v || intensify {
+                self.out.write(b" ")?;
+            }
+            self.out.write_verbatim(key.as_bytes())?;
         }
 
         self.suffix_that_shit()
     }
 
     fn serialize_bytes(self, v: &[u8]) -> Result<Self::Ok, Self::Error> {
-        self.prefix_that_shit(b"\n")?;
-        for i in v {
-            self.writer.write_fmt(format_args!("{} that shit\n", *i))?;
-        }
-        self.writer.write_all(b"oh yeah\n")?;
-        Ok(())
+        self.prefix_that_shit(b" ")?;
+        self.out.write(base64::encode(v).as_bytes())?;
+        self.suffix_that_shit()
     }
 
     fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
-        self.writer.write_all(b"in theory")?;
+        let keyed = self.frames.last().and_then(Frame::key).is_some();
+        // A map entry is data in its own right, so only struct fields go
+        if self.options.omit_none && matches!(self.frames.last(), Some(Frame::Field(_))) {
+            return Ok(());
+        }
+        if keyed {
+            self.start_entry()?;
+        }
+        self.out
+            .write(words(&self.options.dialect).in_theory.as_bytes())?;
 
-        if let Some(key) = self.field_stack.last() {
-            self.writer.write_all(b" ")?;
-            self.writer.write_all(key.as_bytes())?;
+        if let Some(key) = self.frames.last().and_then(Frame::key) {
+            self.out.write(b" ")?;
+            self.out.write_verbatim(key.as_bytes())?;
         }
 
         self.suffix_that_shit()
@@ -211,13 +655,13 @@ impl<'a, W: Write> Serializer for &'a mut TsonSerializer<W> {
 
     fn serialize_unit(self) -> Result<Self::Ok, Self::Error> {
         self.prefix_that_shit(b" ")?;
-        self.writer.write_all(b"unit")?;
+        self.out.write(b"unit")?;
         self.suffix_that_shit()
     }
 
     fn serialize_unit_struct(self, name: &'static str) -> Result<Self::Ok, Self::Error> {
         self.prefix_that_shit(b" ")?;
-        self.writer.write_all(name.as_bytes())?;
+        self.out.write(name.as_bytes())?;
         self.suffix_that_shit()
     }
 
@@ -228,7 +672,9 @@ impl<'a, W: Write> Serializer for &'a mut TsonSerializer<W> {
         variant: &'static str,
     ) -> Result<Self::Ok, Self::Error> {
         self.prefix_that_shit(b" ")?;
-        self.writer.write_all(variant.as_bytes())?;
+        self.out.write_verbatim(
+            identifier(Cow::Borrowed(variant), self.options.dialect.as_ref()).as_bytes(),
+        )?;
         self.suffix_that_shit()
     }
 
@@ -253,24 +699,23 @@ impl<'a, W: Write> Serializer for &'a mut TsonSerializer<W> {
     where
         T: ?Sized + Serialize,
     {
-        self.prefix_that_variant_type_shit(variant)?;
+        self.prefix_that_variant_type_shit(&identifier(
+            Cow::Borrowed(variant),
+            self.options.dialect.as_ref(),
+        ))?;
+        self.open_block();
+        self.frames.push(Frame::Variant);
         value.serialize(&mut *self)?;
-        self.writer.write_all(b"oh yeah\n")?;
-        Ok(())
+        self.frames.pop();
+        self.end_variant()
     }
 
     fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
-        if self
-            .field_stack
-            .last()
-            .map(|f| f.is_empty())
-            .unwrap_or_default()
-        {
-            self.prefix_that_variant_type_shit("fuckin")?;
+        if self.options.inline_seqs && !self.frames.is_empty() {
+            self.inline = Some(Vec::new());
         } else {
-            self.prefix_that_shit(b"\n")?;
+            self.open_seq()?;
         }
-        self.field_stack.push(Cow::Borrowed(""));
 
         Ok(self)
     }
@@ -294,17 +739,21 @@ impl<'a, W: Write> Serializer for &'a mut TsonSerializer<W> {
         variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleVariant, Self::Error> {
-        self.prefix_that_variant_type_shit(variant)?;
-        self.field_stack.push(Cow::Borrowed(""));
+        self.prefix_that_variant_type_shit(&identifier(
+            Cow::Borrowed(variant),
+            self.options.dialect.as_ref(),
+        ))?;
+        self.open_block();
+        self.frames.push(Frame::Variant);
 
         Ok(self)
     }
 
-    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
-        if !self.field_stack.is_empty() {
-            self.writer.write_all(b"\n")?;
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
+        self.open_map(len)?;
+        if self.options.canonical {
+            self.sorted.push(Vec::new());
         }
-        self.prefix_that_shit(b"\n")?;
 
         Ok(self)
     }
@@ -314,7 +763,9 @@ impl<'a, W: Write> Serializer for &'a mut TsonSerializer<W> {
         _name: &'static str,
         len: usize,
     ) -> Result<Self::SerializeStruct, Self::Error> {
-        self.serialize_map(Some(len))
+        self.open_map(Some(len))?;
+
+        Ok(self)
     }
 
     fn serialize_struct_variant(
@@ -324,27 +775,33 @@ impl<'a, W: Write> Serializer for &'a mut TsonSerializer<W> {
         variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeStructVariant, Self::Error> {
-        self.prefix_that_variant_type_shit(variant)?;
+        self.prefix_that_variant_type_shit(&identifier(
+            Cow::Borrowed(variant),
+            self.options.dialect.as_ref(),
+        ))?;
+        self.open_block();
 
         Ok(self)
     }
 }
 
 pub struct MapKeySerializer<'a, W: Write> {
-    field_stack: &'a mut Vec<Cow<'static, str>>,
+    frames: &'a mut Vec<Frame>,
+    options: &'a SerializerOptions,
     called: bool,
     writer: PhantomData<W>,
 }
 
 impl<'a, W: Write> MapKeySerializer<'a, W> {
     fn push(&mut self, value: Cow<'static, str>) -> Result<(), Error> {
+        self.push_verbatim(identifier(value, self.options.dialect.as_ref()))
+    }
+
+    fn push_verbatim(&mut self, value: Cow<'static, str>) -> Result<(), Error> {
         if self.called {
             return Err(Error::AlreadyCalled);
         }
-        if value.as_ref() == "in theory" || value.split_whitespace().count() > 1 {
-            return Err(Error::KeyMustBeStringable);
-        }
-        self.field_stack.push(value);
+        self.frames.push(Frame::Entry(value));
         self.called = true;
 
         Ok(())
@@ -359,6 +816,20 @@ macro_rules! serialize_key_value {
     };
 }
 
+macro_rules! serialize_key_float {
+    ($fn_name:ident, $v:ty) => {
+        fn $fn_name(self, v: $v) -> Result<Self::Ok, Self::Error> {
+            if v.is_finite() {
+                self.push(Cow::Owned(format!("{}", v)))
+            } else if self.options.non_finite_floats {
+                self.push(Cow::Borrowed(non_finite(v.into())))
+            } else {
+                Err(Error::FloatMustBeFinite)
+            }
+        }
+    };
+}
+
 macro_rules! cannot_serialize {
     ($fn_name:ident, $v:ty) => {
         fn $fn_name(self, _v: $v) -> Result<Self::Ok, Self::Error> {
@@ -371,10 +842,10 @@ impl<'a, W: Write> Serializer for &'a mut MapKeySerializer<'a, W> {
     type Ok = ();
 
     type Error = Error;
-    type SerializeSeq = &'a mut TsonSerializer<W>;
-    type SerializeTuple = &'a mut TsonSerializer<W>;
-    type SerializeTupleStruct = &'a mut TsonSerializer<W>;
-    type SerializeTupleVariant = &'a mut TsonSerializer<W>;
+    type SerializeSeq = CompositeKey<'a, W>;
+    type SerializeTuple = CompositeKey<'a, W>;
+    type SerializeTupleStruct = CompositeKey<'a, W>;
+    type SerializeTupleVariant = CompositeKey<'a, W>;
     type SerializeMap = &'a mut TsonSerializer<W>;
     type SerializeStruct = &'a mut TsonSerializer<W>;
     type SerializeStructVariant = &'a mut TsonSerializer<W>;
@@ -383,12 +854,14 @@ impl<'a, W: Write> Serializer for &'a mut MapKeySerializer<'a, W> {
     serialize_key_value!(serialize_i16, i16);
     serialize_key_value!(serialize_i32, i32);
     serialize_key_value!(serialize_i64, i64);
+    serialize_key_value!(serialize_i128, i128);
     serialize_key_value!(serialize_u8, u8);
     serialize_key_value!(serialize_u16, u16);
     serialize_key_value!(serialize_u32, u32);
     serialize_key_value!(serialize_u64, u64);
-    serialize_key_value!(serialize_f32, f32);
-    serialize_key_value!(serialize_f64, f64);
+    serialize_key_value!(serialize_u128, u128);
+    serialize_key_float!(serialize_f32, f32);
+    serialize_key_float!(serialize_f64, f64);
     serialize_key_value!(serialize_char, char);
     serialize_key_value!(serialize_str, &str);
 
@@ -403,7 +876,8 @@ impl<'a, W: Write> Serializer for &'a mut MapKeySerializer<'a, W> {
     }
 
     fn serialize_none(self) -> Result<Self::Ok, Self::Error> {
-        self.push(Cow::Borrowed("in theory"))
+        let in_theory = words(&self.options.dialect).in_theory.clone();
+        self.push_verbatim(in_theory)
     }
 
     fn serialize_some<T>(self, value: &T) -> Result<Self::Ok, Self::Error>
@@ -445,21 +919,23 @@ impl<'a, W: Write> Serializer for &'a mut MapKeySerializer<'a, W> {
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
-        _value: &T,
+        variant: &'static str,
+        value: &T,
     ) -> Result<Self::Ok, Self::Error>
     where
         T: ?Sized + Serialize,
     {
-        Err(Error::KeyMustBeStringable)
+        let mut parts = CompositeKeySerializer::new(self.options, Some(variant));
+        parts.part(value)?;
+        self.push(Cow::Owned(parts.finish()))
     }
 
     fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Self::Error> {
-        Err(Error::KeyMustBeStringable)
+        Ok(CompositeKey::new(self, None))
     }
 
     fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Self::Error> {
-        Err(Error::KeyMustBeStringable)
+        Ok(CompositeKey::new(self, None))
     }
 
     fn serialize_tuple_struct(
@@ -467,17 +943,17 @@ impl<'a, W: Write> Serializer for &'a mut MapKeySerializer<'a, W> {
         _name: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleStruct, Self::Error> {
-        Err(Error::KeyMustBeStringable)
+        Ok(CompositeKey::new(self, None))
     }
 
     fn serialize_tuple_variant(
         self,
         _name: &'static str,
         _variant_index: u32,
-        _variant: &'static str,
+        variant: &'static str,
         _len: usize,
     ) -> Result<Self::SerializeTupleVariant, Self::Error> {
-        Err(Error::KeyMustBeStringable)
+        Ok(CompositeKey::new(self, Some(variant)))
     }
 
     fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Self::Error> {
@@ -504,16 +980,246 @@ impl<'a, W: Write> Serializer for &'a mut MapKeySerializer<'a, W> {
 }
 
 impl<'a, W: Write> MapKeySerializer<'a, W> {
-    fn new(field_stack: &'a mut Vec<Cow<'static, str>>) -> MapKeySerializer<'a, W> {
+    fn new(frames: &'a mut Vec<Frame>, options: &'a SerializerOptions) -> MapKeySerializer<'a, W> {
         Self {
-            field_stack,
+            frames,
+            options,
             called: false,
             writer: PhantomData,
         }
     }
 }
 
-impl<'a, W: Write> SerializeSeq for &'a mut TsonSerializer<W> {
+/// Writes a tuple, tuple struct, sequence or tuple variant key as `(a,b)` or
+/// `Variant(a,b)`
+pub struct CompositeKey<'a, W: Write> {
+    key: &'a mut MapKeySerializer<'a, W>,
+    parts: CompositeKeySerializer<'a>,
+}
+
+impl<'a, W: Write> CompositeKey<'a, W> {
+    fn new(key: &'a mut MapKeySerializer<'a, W>, variant: Option<&str>) -> Self {
+        let parts = CompositeKeySerializer::new(key.options, variant);
+        Self { key, parts }
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.key.push(Cow::Owned(self.parts.finish()))
+    }
+}
+
+macro_rules! serialize_composite_key {
+    ($trait:ident, $fn_name:ident) => {
+        impl<W: Write> $trait for CompositeKey<'_, W> {
+            type Ok = ();
+
+            type Error = Error;
+
+            fn $fn_name<T>(&mut self, value: &T) -> Result<(), Self::Error>
+            where
+                T: ?Sized + Serialize,
+            {
+                self.parts.part(value)
+            }
+
+            fn end(self) -> Result<Self::Ok, Self::Error> {
+                CompositeKey::end(self)
+            }
+        }
+    };
+}
+
+serialize_composite_key!(SerializeSeq, serialize_element);
+serialize_composite_key!(SerializeTuple, serialize_element);
+serialize_composite_key!(SerializeTupleStruct, serialize_field);
+serialize_composite_key!(SerializeTupleVariant, serialize_field);
+
+/// Takes an element of a sequence as a [`Scalar`] while the sequence may still
+/// be written on one line, handing anything else to the serializer itself.
+/// Elements go through here once, as some can only be serialized once
+struct ElementSerializer<'a, W: Write>(&'a mut TsonSerializer<W>);
+
+impl<'a, W: Write> ElementSerializer<'a, W> {
+    /// Writes the elements held so far, leaving the sequence a block
+    fn block(self) -> Result<&'a mut TsonSerializer<W>, Error> {
+        self.0.write_held_elements()?;
+        Ok(self.0)
+    }
+}
+
+macro_rules! serialize_scalar_element {
+    ($fn_name:ident, $v:ty, $variant:ident) => {
+        fn $fn_name(self, v: $v) -> Result<(), Error> {
+            match &mut self.0.inline {
+                Some(inline) => {
+                    inline.push(Scalar::$variant(v.into()));
+                    Ok(())
+                }
+                None => self.0.$fn_name(v),
+            }
+        }
+    };
+}
+
+impl<'a, W: Write> Serializer for ElementSerializer<'a, W> {
+    type Ok = ();
+
+    type Error = Error;
+    type SerializeSeq = &'a mut TsonSerializer<W>;
+    type SerializeTuple = &'a mut TsonSerializer<W>;
+    type SerializeTupleStruct = &'a mut TsonSerializer<W>;
+    type SerializeTupleVariant = &'a mut TsonSerializer<W>;
+    type SerializeMap = &'a mut TsonSerializer<W>;
+    type SerializeStruct = &'a mut TsonSerializer<W>;
+    type SerializeStructVariant = &'a mut TsonSerializer<W>;
+
+    serialize_scalar_element!(serialize_i8, i8, Int);
+    serialize_scalar_element!(serialize_i16, i16, Int);
+    serialize_scalar_element!(serialize_i32, i32, Int);
+    serialize_scalar_element!(serialize_i64, i64, Int);
+    serialize_scalar_element!(serialize_i128, i128, Int);
+    serialize_scalar_element!(serialize_u8, u8, UInt);
+    serialize_scalar_element!(serialize_u16, u16, UInt);
+    serialize_scalar_element!(serialize_u32, u32, UInt);
+    serialize_scalar_element!(serialize_u64, u64, UInt);
+    serialize_scalar_element!(serialize_u128, u128, UInt);
+    serialize_scalar_element!(serialize_f32, f32, F32);
+    serialize_scalar_element!(serialize_f64, f64, F64);
+    serialize_scalar_element!(serialize_str, &str, Str);
+
+    fn serialize_char(self, v: char) -> Result<(), Error> {
+        let mut ch = [0; 4];
+        self.serialize_str(v.encode_utf8(&mut ch))
+    }
+
+    /// Bools are written as intensifiers, which can't sit inside `[...]`
+    fn serialize_bool(self, v: bool) -> Result<(), Error> {
+        self.block()?.serialize_bool(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+        self.block()?.serialize_bytes(v)
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        self.block()?.serialize_none()
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        match &mut self.0.inline {
+            Some(inline) => {
+                inline.push(Scalar::Unit);
+                Ok(())
+            }
+            None => self.0.serialize_unit(),
+        }
+    }
+
+    fn serialize_unit_struct(self, name: &'static str) -> Result<(), Error> {
+        match &mut self.0.inline {
+            Some(inline) => {
+                inline.push(Scalar::UnitStruct(name));
+                Ok(())
+            }
+            None => self.0.serialize_unit_struct(name),
+        }
+    }
+
+    fn serialize_unit_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        match &mut self.0.inline {
+            Some(inline) => {
+                inline.push(Scalar::UnitVariant(variant));
+                Ok(())
+            }
+            None => self.0.serialize_unit_variant(name, variant_index, variant),
+        }
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.block()?
+            .serialize_newtype_variant(name, variant_index, variant, value)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        self.block()?.serialize_seq(len)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Self::SerializeTuple, Error> {
+        self.block()?.serialize_tuple(len)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        self.block()?.serialize_tuple_struct(name, len)
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        self.block()?
+            .serialize_tuple_variant(name, variant_index, variant, len)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        self.block()?.serialize_map(len)
+    }
+
+    fn serialize_struct(
+        self,
+        name: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStruct, Error> {
+        self.block()?.serialize_struct(name, len)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        name: &'static str,
+        variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        self.block()?
+            .serialize_struct_variant(name, variant_index, variant, len)
+    }
+}
+
+impl<W: Write> SerializeSeq for &mut TsonSerializer<W> {
     type Ok = ();
 
     type Error = Error;
@@ -522,16 +1228,15 @@ impl<'a, W: Write> SerializeSeq for &'a mut TsonSerializer<W> {
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        TsonSerializer::serialize_element(self, value)
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        self.field_stack.pop();
-        self.end_struct()
+        self.end_seq()
     }
 }
 
-impl<'a, W: Write> SerializeTuple for &'a mut TsonSerializer<W> {
+impl<W: Write> SerializeTuple for &mut TsonSerializer<W> {
     type Ok = ();
 
     type Error = Error;
@@ -540,16 +1245,15 @@ impl<'a, W: Write> SerializeTuple for &'a mut TsonSerializer<W> {
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        TsonSerializer::serialize_element(self, value)
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        self.field_stack.pop();
-        self.end_struct()
+        self.end_seq()
     }
 }
 
-impl<'a, W: Write> SerializeTupleStruct for &'a mut TsonSerializer<W> {
+impl<W: Write> SerializeTupleStruct for &mut TsonSerializer<W> {
     type Ok = ();
 
     type Error = Error;
@@ -558,16 +1262,15 @@ impl<'a, W: Write> SerializeTupleStruct for &'a mut TsonSerializer<W> {
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)
+        TsonSerializer::serialize_element(self, value)
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        self.field_stack.pop();
-        self.end_struct()
+        self.end_seq()
     }
 }
 
-impl<'a, W: Write> SerializeTupleVariant for &'a mut TsonSerializer<W> {
+impl<W: Write> SerializeTupleVariant for &mut TsonSerializer<W> {
     type Ok = ();
 
     type Error = Error;
@@ -580,12 +1283,12 @@ impl<'a, W: Write> SerializeTupleVariant for &'a mut TsonSerializer<W> {
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        self.field_stack.pop();
-        self.end_struct()
+        self.frames.pop();
+        self.end_variant()
     }
 }
 
-impl<'a, W: Write> SerializeMap for &'a mut TsonSerializer<W> {
+impl<W: Write> SerializeMap for &mut TsonSerializer<W> {
     type Ok = ();
 
     type Error = Error;
@@ -594,24 +1297,61 @@ impl<'a, W: Write> SerializeMap for &'a mut TsonSerializer<W> {
     where
         T: ?Sized + Serialize,
     {
-        key.serialize(&mut MapKeySerializer::<W>::new(&mut self.field_stack))
+        key.serialize(&mut MapKeySerializer::<W>::new(
+            &mut self.frames,
+            &self.options,
+        ))
     }
 
     fn serialize_value<T>(&mut self, value: &T) -> Result<(), Self::Error>
     where
         T: ?Sized + Serialize,
     {
-        value.serialize(&mut **self)?;
-        self.field_stack.pop();
+        if !self.options.canonical {
+            value.serialize(&mut **self)?;
+            self.frames.pop();
+            return Ok(());
+        }
+
+        let Some(frame) = self.frames.pop() else {
+            return Err(Error::Custom(String::from("map value without a key")));
+        };
+        let key = frame.key().unwrap_or_default().to_owned();
+        let mut entry = TsonSerializer {
+            out: Output {
+                writer: Vec::new(),
+                indent: self.out.indent.clone(),
+                depth: self.out.depth,
+                line_start: true,
+                header: String::new(),
+            },
+            frames: vec![frame],
+            options: self.options.clone(),
+            first_entry: true,
+            sorted: Vec::new(),
+            inline: None,
+        };
+        value.serialize(&mut entry)?;
+        if let Some(entries) = self.sorted.last_mut() {
+            entries.push((key, entry.out.writer));
+        }
         Ok(())
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
+        if self.options.canonical {
+            let mut entries = self.sorted.pop().unwrap_or_default();
+            entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+            for (_, entry) in entries.iter().filter(|(_, entry)| !entry.is_empty()) {
+                self.start_entry()?;
+                self.out.write_lines(entry)?;
+            }
+        }
         self.end_struct()
     }
 }
 
-impl<'a, W: Write> SerializeStruct for &'a mut TsonSerializer<W> {
+impl<W: Write> SerializeStruct for &mut TsonSerializer<W> {
     type Ok = ();
 
     type Error = Error;
@@ -628,7 +1368,7 @@ impl<'a, W: Write> SerializeStruct for &'a mut TsonSerializer<W> {
     }
 }
 
-impl<'a, W: Write> SerializeStructVariant for &'a mut TsonSerializer<W> {
+impl<W: Write> SerializeStructVariant for &mut TsonSerializer<W> {
     type Ok = ();
 
     type Error = Error;
@@ -641,6 +1381,30 @@ impl<'a, W: Write> SerializeStructVariant for &'a mut TsonSerializer<W> {
     }
 
     fn end(self) -> Result<Self::Ok, Self::Error> {
-        self.end_struct()
+        self.end_variant()
+    }
+}
+
+/// 64-bit FNV-1a, which unlike [`std::hash::DefaultHasher`] is the same on
+/// every platform and release
+pub(crate) struct Fnv1a(pub(crate) u64);
+
+impl Default for Fnv1a {
+    fn default() -> Self {
+        Self(0xcbf2_9ce4_8422_2325)
+    }
+}
+
+impl Write for Fnv1a {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        for byte in buf {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
     }
 }
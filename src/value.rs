@@ -0,0 +1,1271 @@
+use std::{
+    collections::{BTreeMap, btree_map},
+    fmt::{self, Display},
+    ops, vec,
+};
+
+use serde_core::{
+    Deserialize, Deserializer, Serialize, Serializer,
+    de::{
+        DeserializeOwned, DeserializeSeed, EnumAccess, Error as _, Expected, IntoDeserializer,
+        MapAccess, SeqAccess, Unexpected, VariantAccess, Visitor,
+    },
+    ser::{
+        self, SerializeMap, SerializeSeq, SerializeStruct, SerializeStructVariant, SerializeTuple,
+        SerializeTupleStruct, SerializeTupleVariant,
+    },
+};
+
+use crate::{
+    SerializerOptions, base64, de,
+    key::{self, KeyPartSerializer},
+    ser::Error,
+};
+
+pub type Map = BTreeMap<String, Value>;
+
+/// Any TSON document, for when there is no Rust type to load it into
+#[derive(Debug, Clone, PartialEq, Default)]
+pub enum Value {
+    Bool(bool),
+    #[default]
+    None,
+    Unit,
+    String(String),
+    Number(Number),
+    Seq(Vec<Value>),
+    Map(Map),
+}
+
+/// Integers are only held in 128 bits when they don't fit in 64, so each
+/// number has a single representation
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Number {
+    Unsigned(u64),
+    Signed(i64),
+    Unsigned128(u128),
+    Signed128(i128),
+    Float(f64),
+}
+
+impl Display for Number {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            Number::Unsigned(n) => write!(f, "{}", n),
+            Number::Signed(n) => write!(f, "{}", n),
+            Number::Unsigned128(n) => write!(f, "{}", n),
+            Number::Signed128(n) => write!(f, "{}", n),
+            Number::Float(n) => write!(f, "{}", n),
+        }
+    }
+}
+
+impl Value {
+    /// Looks up a value by map key or sequence position, giving `None` if the
+    /// key or position is missing or this is the wrong kind of value
+    pub fn get<I: Index>(&self, index: I) -> Option<&Value> {
+        index.index_into(self)
+    }
+
+    pub fn get_mut<I: Index>(&mut self, index: I) -> Option<&mut Value> {
+        index.index_into_mut(self)
+    }
+
+    pub fn is_none(&self) -> bool {
+        matches!(self, Value::None)
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match *self {
+            Value::Bool(b) => Some(b),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Value::Number(Number::Unsigned(n)) => Some(n),
+            Value::Number(Number::Signed(n)) => n.try_into().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_i64(&self) -> Option<i64> {
+        match *self {
+            Value::Number(Number::Unsigned(n)) => n.try_into().ok(),
+            Value::Number(Number::Signed(n)) => Some(n),
+            _ => None,
+        }
+    }
+
+    pub fn as_u128(&self) -> Option<u128> {
+        match *self {
+            Value::Number(Number::Unsigned(n)) => Some(n.into()),
+            Value::Number(Number::Signed(n)) => n.try_into().ok(),
+            Value::Number(Number::Unsigned128(n)) => Some(n),
+            Value::Number(Number::Signed128(n)) => n.try_into().ok(),
+            _ => None,
+        }
+    }
+
+    pub fn as_i128(&self) -> Option<i128> {
+        match *self {
+            Value::Number(Number::Unsigned(n)) => Some(n.into()),
+            Value::Number(Number::Signed(n)) => Some(n.into()),
+            Value::Number(Number::Unsigned128(n)) => n.try_into().ok(),
+            Value::Number(Number::Signed128(n)) => Some(n),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match *self {
+            Value::Number(Number::Unsigned(n)) => Some(n as f64),
+            Value::Number(Number::Signed(n)) => Some(n as f64),
+            Value::Number(Number::Unsigned128(n)) => Some(n as f64),
+            Value::Number(Number::Signed128(n)) => Some(n as f64),
+            Value::Number(Number::Float(n)) => Some(n),
+            _ => None,
+        }
+    }
+
+    pub fn as_seq(&self) -> Option<&Vec<Value>> {
+        match self {
+            Value::Seq(seq) => Some(seq),
+            _ => None,
+        }
+    }
+
+    pub fn as_seq_mut(&mut self) -> Option<&mut Vec<Value>> {
+        match self {
+            Value::Seq(seq) => Some(seq),
+            _ => None,
+        }
+    }
+
+    pub fn as_map(&self) -> Option<&Map> {
+        match self {
+            Value::Map(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    pub fn as_map_mut(&mut self) -> Option<&mut Map> {
+        match self {
+            Value::Map(map) => Some(map),
+            _ => None,
+        }
+    }
+
+    #[cold]
+    fn unexpected(&self) -> Unexpected<'_> {
+        match self {
+            Value::Bool(b) => Unexpected::Bool(*b),
+            Value::None => Unexpected::Option,
+            Value::Unit => Unexpected::Unit,
+            Value::String(s) => Unexpected::Str(s),
+            Value::Number(Number::Unsigned(n)) => Unexpected::Unsigned(*n),
+            Value::Number(Number::Signed(n)) => Unexpected::Signed(*n),
+            Value::Number(Number::Unsigned128(_) | Number::Signed128(_)) => {
+                Unexpected::Other("128-bit integer")
+            }
+            Value::Number(Number::Float(n)) => Unexpected::Float(*n),
+            Value::Seq(_) => Unexpected::Seq,
+            Value::Map(_) => Unexpected::Map,
+        }
+    }
+
+    #[cold]
+    fn invalid_type<E: serde_core::de::Error>(&self, exp: &dyn Expected) -> E {
+        E::invalid_type(self.unexpected(), exp)
+    }
+}
+
+mod private {
+    pub trait Sealed {}
+    impl Sealed for usize {}
+    impl Sealed for str {}
+    impl Sealed for String {}
+    impl<T: ?Sized + Sealed> Sealed for &T {}
+}
+
+/// Something a [`Value`] can be indexed by: a `usize` into a sequence or a
+/// string into a map
+pub trait Index: private::Sealed {
+    #[doc(hidden)]
+    fn index_into<'v>(&self, v: &'v Value) -> Option<&'v Value>;
+
+    #[doc(hidden)]
+    fn index_into_mut<'v>(&self, v: &'v mut Value) -> Option<&'v mut Value>;
+
+    #[doc(hidden)]
+    fn index_or_insert<'v>(&self, v: &'v mut Value) -> &'v mut Value;
+}
+
+impl Index for usize {
+    fn index_into<'v>(&self, v: &'v Value) -> Option<&'v Value> {
+        v.as_seq()?.get(*self)
+    }
+
+    fn index_into_mut<'v>(&self, v: &'v mut Value) -> Option<&'v mut Value> {
+        v.as_seq_mut()?.get_mut(*self)
+    }
+
+    fn index_or_insert<'v>(&self, v: &'v mut Value) -> &'v mut Value {
+        match v {
+            Value::Seq(seq) => {
+                let len = seq.len();
+                seq.get_mut(*self).unwrap_or_else(|| {
+                    panic!(
+                        "cannot access index {} of a sequence of length {}",
+                        self, len
+                    )
+                })
+            }
+            _ => panic!("cannot access index {} of {:?}", self, v.unexpected()),
+        }
+    }
+}
+
+impl Index for str {
+    fn index_into<'v>(&self, v: &'v Value) -> Option<&'v Value> {
+        v.as_map()?.get(self)
+    }
+
+    fn index_into_mut<'v>(&self, v: &'v mut Value) -> Option<&'v mut Value> {
+        v.as_map_mut()?.get_mut(self)
+    }
+
+    fn index_or_insert<'v>(&self, v: &'v mut Value) -> &'v mut Value {
+        if v.is_none() {
+            *v = Value::Map(Map::new());
+        }
+        match v {
+            Value::Map(map) => map.entry(self.to_owned()).or_default(),
+            _ => panic!("cannot access key {:?} of {:?}", self, v.unexpected()),
+        }
+    }
+}
+
+impl Index for String {
+    fn index_into<'v>(&self, v: &'v Value) -> Option<&'v Value> {
+        self.as_str().index_into(v)
+    }
+
+    fn index_into_mut<'v>(&self, v: &'v mut Value) -> Option<&'v mut Value> {
+        self.as_str().index_into_mut(v)
+    }
+
+    fn index_or_insert<'v>(&self, v: &'v mut Value) -> &'v mut Value {
+        self.as_str().index_or_insert(v)
+    }
+}
+
+impl<T: ?Sized + Index> Index for &T {
+    fn index_into<'v>(&self, v: &'v Value) -> Option<&'v Value> {
+        (**self).index_into(v)
+    }
+
+    fn index_into_mut<'v>(&self, v: &'v mut Value) -> Option<&'v mut Value> {
+        (**self).index_into_mut(v)
+    }
+
+    fn index_or_insert<'v>(&self, v: &'v mut Value) -> &'v mut Value {
+        (**self).index_or_insert(v)
+    }
+}
+
+static NONE: Value = Value::None;
+
+/// Missing keys and positions index to [`Value::None`]
+impl<I: Index> ops::Index<I> for Value {
+    type Output = Value;
+
+    fn index(&self, index: I) -> &Value {
+        index.index_into(self).unwrap_or(&NONE)
+    }
+}
+
+/// Missing keys are inserted as [`Value::None`], turning a `None` into a map
+/// first if need be. Missing positions panic.
+impl<I: Index> ops::IndexMut<I> for Value {
+    fn index_mut(&mut self, index: I) -> &mut Value {
+        index.index_or_insert(self)
+    }
+}
+
+macro_rules! from_number {
+    ($variant:ident, $($ty:ty),*) => {
+        $(
+            impl From<$ty> for Value {
+                fn from(n: $ty) -> Self {
+                    Value::Number(Number::$variant(n.into()))
+                }
+            }
+        )*
+    };
+}
+
+from_number!(Unsigned, u8, u16, u32, u64);
+from_number!(Signed, i8, i16, i32, i64);
+from_number!(Float, f32, f64);
+
+impl From<u128> for Value {
+    fn from(n: u128) -> Self {
+        Value::Number(match u64::try_from(n) {
+            Ok(n) => Number::Unsigned(n),
+            Err(_) => Number::Unsigned128(n),
+        })
+    }
+}
+
+impl From<i128> for Value {
+    fn from(n: i128) -> Self {
+        Value::Number(match i64::try_from(n) {
+            Ok(n) => Number::Signed(n),
+            Err(_) => Number::Signed128(n),
+        })
+    }
+}
+
+impl From<bool> for Value {
+    fn from(b: bool) -> Self {
+        Value::Bool(b)
+    }
+}
+
+impl From<()> for Value {
+    fn from(_: ()) -> Self {
+        Value::Unit
+    }
+}
+
+impl From<&str> for Value {
+    fn from(s: &str) -> Self {
+        Value::String(s.to_owned())
+    }
+}
+
+impl From<String> for Value {
+    fn from(s: String) -> Self {
+        Value::String(s)
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::None, Into::into)
+    }
+}
+
+impl<T: Into<Value>> From<Vec<T>> for Value {
+    fn from(seq: Vec<T>) -> Self {
+        Value::Seq(seq.into_iter().map(Into::into).collect())
+    }
+}
+
+impl From<Map> for Value {
+    fn from(map: Map) -> Self {
+        Value::Map(map)
+    }
+}
+
+/// `null` becomes [`Value::Unit`], as it does when JSON is read straight into
+/// a `Value`
+#[cfg(feature = "serde_json")]
+impl From<serde_json::Value> for Value {
+    fn from(value: serde_json::Value) -> Self {
+        match value {
+            serde_json::Value::Null => Value::Unit,
+            serde_json::Value::Bool(b) => Value::Bool(b),
+            serde_json::Value::Number(n) => Value::Number(if let Some(n) = n.as_u64() {
+                Number::Unsigned(n)
+            } else if let Some(n) = n.as_i64() {
+                Number::Signed(n)
+            } else {
+                Number::Float(n.as_f64().unwrap_or(f64::NAN))
+            }),
+            serde_json::Value::String(s) => Value::String(s),
+            serde_json::Value::Array(seq) => Value::Seq(seq.into_iter().map(Value::from).collect()),
+            serde_json::Value::Object(map) => Value::Map(
+                map.into_iter()
+                    .map(|(key, value)| (key, Value::from(value)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+/// `None` and `Unit` both become `null`, as do floats JSON can't represent.
+/// Integers too big for 64 bits become their decimal text
+#[cfg(feature = "serde_json")]
+impl From<Value> for serde_json::Value {
+    fn from(value: Value) -> Self {
+        match value {
+            Value::None | Value::Unit => serde_json::Value::Null,
+            Value::Bool(b) => serde_json::Value::Bool(b),
+            Value::Number(Number::Unsigned(n)) => serde_json::Value::from(n),
+            Value::Number(Number::Signed(n)) => serde_json::Value::from(n),
+            Value::Number(n @ (Number::Unsigned128(_) | Number::Signed128(_))) => {
+                serde_json::Value::String(n.to_string())
+            }
+            Value::Number(Number::Float(n)) => serde_json::Value::from(n),
+            Value::String(s) => serde_json::Value::String(s),
+            Value::Seq(seq) => {
+                serde_json::Value::Array(seq.into_iter().map(serde_json::Value::from).collect())
+            }
+            Value::Map(map) => serde_json::Value::Object(
+                map.into_iter()
+                    .map(|(key, value)| (key, serde_json::Value::from(value)))
+                    .collect(),
+            ),
+        }
+    }
+}
+
+impl Serialize for Value {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self {
+            Value::Bool(b) => serializer.serialize_bool(*b),
+            Value::None => serializer.serialize_none(),
+            Value::Unit => serializer.serialize_unit(),
+            Value::String(s) => serializer.serialize_str(s),
+            Value::Number(n) => n.serialize(serializer),
+            Value::Seq(seq) => seq.serialize(serializer),
+            Value::Map(map) => map.serialize(serializer),
+        }
+    }
+}
+
+impl Serialize for Number {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match *self {
+            Number::Unsigned(n) => serializer.serialize_u64(n),
+            Number::Signed(n) => serializer.serialize_i64(n),
+            Number::Unsigned128(n) => serializer.serialize_u128(n),
+            Number::Signed128(n) => serializer.serialize_i128(n),
+            Number::Float(n) => serializer.serialize_f64(n),
+        }
+    }
+}
+
+struct ValueVisitor;
+
+impl<'de> Visitor<'de> for ValueVisitor {
+    type Value = Value;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("any TSON value")
+    }
+
+    fn visit_bool<E>(self, v: bool) -> Result<Value, E> {
+        Ok(Value::Bool(v))
+    }
+
+    fn visit_i64<E>(self, v: i64) -> Result<Value, E> {
+        Ok(Value::Number(Number::Signed(v)))
+    }
+
+    fn visit_u64<E>(self, v: u64) -> Result<Value, E> {
+        Ok(Value::Number(Number::Unsigned(v)))
+    }
+
+    fn visit_i128<E>(self, v: i128) -> Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_u128<E>(self, v: u128) -> Result<Value, E> {
+        Ok(Value::from(v))
+    }
+
+    fn visit_f64<E>(self, v: f64) -> Result<Value, E> {
+        Ok(Value::Number(Number::Float(v)))
+    }
+
+    fn visit_str<E>(self, v: &str) -> Result<Value, E> {
+        Ok(Value::String(v.to_owned()))
+    }
+
+    fn visit_string<E>(self, v: String) -> Result<Value, E> {
+        Ok(Value::String(v))
+    }
+
+    /// Bytes are kept as the `base64:` text they are written as
+    fn visit_bytes<E>(self, v: &[u8]) -> Result<Value, E> {
+        Ok(Value::String(base64::encode(v)))
+    }
+
+    fn visit_none<E>(self) -> Result<Value, E> {
+        Ok(Value::None)
+    }
+
+    fn visit_some<D>(self, deserializer: D) -> Result<Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Value::deserialize(deserializer)
+    }
+
+    fn visit_unit<E>(self) -> Result<Value, E> {
+        Ok(Value::Unit)
+    }
+
+    fn visit_newtype_struct<D>(self, deserializer: D) -> Result<Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Value::deserialize(deserializer)
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Value, A::Error>
+    where
+        A: SeqAccess<'de>,
+    {
+        let mut values = Vec::with_capacity(seq.size_hint().unwrap_or_default());
+        while let Some(value) = seq.next_element()? {
+            values.push(value);
+        }
+        Ok(Value::Seq(values))
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut values = Map::new();
+        while let Some((key, value)) = map.next_entry()? {
+            values.insert(key, value);
+        }
+        Ok(Value::Map(values))
+    }
+}
+
+impl<'de> Deserialize<'de> for Value {
+    fn deserialize<D>(deserializer: D) -> Result<Value, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(ValueVisitor)
+    }
+}
+
+/// Converts any serializable type into a [`Value`]
+pub fn to_value<T: ?Sized + Serialize>(value: &T) -> Result<Value, Error> {
+    value.serialize(ValueSerializer)
+}
+
+/// Reads any deserializable type back out of a [`Value`]
+pub fn from_value<T: DeserializeOwned>(value: Value) -> Result<T, de::Error> {
+    T::deserialize(value)
+}
+
+struct ValueSerializer;
+
+impl Serializer for ValueSerializer {
+    type Ok = Value;
+    type Error = Error;
+    type SerializeSeq = SerializeVec;
+    type SerializeTuple = SerializeVec;
+    type SerializeTupleStruct = SerializeVec;
+    type SerializeTupleVariant = SerializeTupleVariantValue;
+    type SerializeMap = SerializeMapValue;
+    type SerializeStruct = SerializeMapValue;
+    type SerializeStructVariant = SerializeStructVariantValue;
+
+    fn serialize_bool(self, v: bool) -> Result<Value, Error> {
+        Ok(Value::Bool(v))
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<Value, Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<Value, Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<Value, Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<Value, Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<Value, Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<Value, Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<Value, Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<Value, Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<Value, Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<Value, Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<Value, Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<Value, Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_char(self, v: char) -> Result<Value, Error> {
+        Ok(Value::String(v.to_string()))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<Value, Error> {
+        Ok(v.into())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<Value, Error> {
+        Ok(Value::String(base64::encode(v)))
+    }
+
+    fn serialize_none(self) -> Result<Value, Error> {
+        Ok(Value::None)
+    }
+
+    fn serialize_some<T>(self, value: &T) -> Result<Value, Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Value, Error> {
+        Ok(Value::Unit)
+    }
+
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<Value, Error> {
+        Ok(Value::Unit)
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+    ) -> Result<Value, Error> {
+        Ok(variant.into())
+    }
+
+    fn serialize_newtype_struct<T>(self, _name: &'static str, value: &T) -> Result<Value, Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<Value, Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let mut map = Map::new();
+        map.insert(variant.to_owned(), to_value(value)?);
+        Ok(Value::Map(map))
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<SerializeVec, Error> {
+        Ok(SerializeVec(Vec::with_capacity(len.unwrap_or_default())))
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SerializeVec, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        len: usize,
+    ) -> Result<SerializeVec, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeTupleVariantValue, Error> {
+        Ok(SerializeTupleVariantValue(
+            variant,
+            SerializeVec(Vec::with_capacity(len)),
+        ))
+    }
+
+    fn serialize_map(self, _len: Option<usize>) -> Result<SerializeMapValue, Error> {
+        Ok(SerializeMapValue {
+            map: Map::new(),
+            key: None,
+        })
+    }
+
+    fn serialize_struct(self, _name: &'static str, len: usize) -> Result<SerializeMapValue, Error> {
+        self.serialize_map(Some(len))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<SerializeStructVariantValue, Error> {
+        Ok(SerializeStructVariantValue(
+            variant,
+            self.serialize_map(Some(len))?,
+        ))
+    }
+}
+
+struct SerializeVec(Vec<Value>);
+
+impl SerializeSeq for SerializeVec {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.0.push(to_value(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(Value::Seq(self.0))
+    }
+}
+
+impl SerializeTuple for SerializeVec {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_element<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        SerializeSeq::end(self)
+    }
+}
+
+impl SerializeTupleStruct for SerializeVec {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        SerializeSeq::serialize_element(self, value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        SerializeSeq::end(self)
+    }
+}
+
+struct SerializeTupleVariantValue(&'static str, SerializeVec);
+
+impl SerializeTupleVariant for SerializeTupleVariantValue {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        SerializeSeq::serialize_element(&mut self.1, value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        let mut map = Map::new();
+        map.insert(self.0.to_owned(), SerializeSeq::end(self.1)?);
+        Ok(Value::Map(map))
+    }
+}
+
+struct SerializeMapValue {
+    map: Map,
+    key: Option<String>,
+}
+
+impl SerializeMap for SerializeMapValue {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_key<T>(&mut self, key: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.key = Some(match to_value(key)? {
+            Value::String(key) => key,
+            Value::Bool(b) => b.to_string(),
+            Value::Number(n) => n.to_string(),
+            Value::Unit => String::from("unit"),
+            // Tuples and variants are kept as the key text TSON writes them as,
+            // which reads back into the same key
+            Value::Seq(_) | Value::Map(_) => key.serialize(KeyPartSerializer {
+                options: &SerializerOptions::new().non_finite_floats(true),
+            })?,
+            Value::None => return Err(Error::KeyMustBeStringable),
+        });
+        Ok(())
+    }
+
+    fn serialize_value<T>(&mut self, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        let key = self
+            .key
+            .take()
+            .ok_or_else(|| <Error as ser::Error>::custom("value serialized before its key"))?;
+        self.map.insert(key, to_value(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        Ok(Value::Map(self.map))
+    }
+}
+
+impl SerializeStruct for SerializeMapValue {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        self.map.insert(key.to_owned(), to_value(value)?);
+        Ok(())
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        SerializeMap::end(self)
+    }
+}
+
+struct SerializeStructVariantValue(&'static str, SerializeMapValue);
+
+impl SerializeStructVariant for SerializeStructVariantValue {
+    type Ok = Value;
+    type Error = Error;
+
+    fn serialize_field<T>(&mut self, key: &'static str, value: &T) -> Result<(), Error>
+    where
+        T: ?Sized + Serialize,
+    {
+        SerializeStruct::serialize_field(&mut self.1, key, value)
+    }
+
+    fn end(self) -> Result<Value, Error> {
+        let mut map = Map::new();
+        map.insert(self.0.to_owned(), SerializeMap::end(self.1)?);
+        Ok(Value::Map(map))
+    }
+}
+
+impl<'de> IntoDeserializer<'de, de::Error> for Value {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+impl<'de> Deserializer<'de> for Value {
+    type Error = de::Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, de::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::Bool(b) => visitor.visit_bool(b),
+            Value::None => visitor.visit_none(),
+            Value::Unit => visitor.visit_unit(),
+            Value::String(s) => visitor.visit_string(s),
+            Value::Number(Number::Unsigned(n)) => visitor.visit_u64(n),
+            Value::Number(Number::Signed(n)) => visitor.visit_i64(n),
+            Value::Number(Number::Unsigned128(n)) => visitor.visit_u128(n),
+            Value::Number(Number::Signed128(n)) => visitor.visit_i128(n),
+            Value::Number(Number::Float(n)) => visitor.visit_f64(n),
+            Value::Seq(seq) => visitor.visit_seq(SeqDeserializer(seq.into_iter())),
+            Value::Map(map) => visitor.visit_map(MapDeserializer {
+                iter: map.into_iter(),
+                value: None,
+            }),
+        }
+    }
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, de::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::None => visitor.visit_none(),
+            value => visitor.visit_some(value),
+        }
+    }
+
+    fn deserialize_bytes<V>(self, visitor: V) -> Result<V::Value, de::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    /// Decodes a string holding a `base64:` block, as bytes are kept in a
+    /// [`Value`]
+    fn deserialize_byte_buf<V>(self, visitor: V) -> Result<V::Value, de::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match &self {
+            Value::String(s) => match base64::decode(s) {
+                Some(bytes) => visitor.visit_byte_buf(bytes),
+                None => self.deserialize_any(visitor),
+            },
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, de::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, de::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self {
+            Value::String(variant) => visitor.visit_enum(EnumDeserializer {
+                variant,
+                value: None,
+            }),
+            Value::Map(map) if map.len() == 1 => {
+                let (variant, value) = map.into_iter().next().unwrap();
+                visitor.visit_enum(EnumDeserializer {
+                    variant,
+                    value: Some(value),
+                })
+            }
+            value => Err(value.invalid_type(&"string or map with a single key")),
+        }
+    }
+
+    serde_core::forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string
+        unit unit_struct seq tuple tuple_struct map struct identifier
+        ignored_any
+    }
+}
+
+struct SeqDeserializer(vec::IntoIter<Value>);
+
+impl<'de> SeqAccess<'de> for SeqDeserializer {
+    type Error = de::Error;
+
+    fn next_element_seed<T>(&mut self, seed: T) -> Result<Option<T::Value>, de::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        self.0
+            .next()
+            .map(|value| seed.deserialize(value))
+            .transpose()
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.0.len())
+    }
+}
+
+struct MapDeserializer {
+    iter: btree_map::IntoIter<String, Value>,
+    value: Option<Value>,
+}
+
+impl<'de> MapAccess<'de> for MapDeserializer {
+    type Error = de::Error;
+
+    fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>, de::Error>
+    where
+        K: DeserializeSeed<'de>,
+    {
+        let Some((key, value)) = self.iter.next() else {
+            return Ok(None);
+        };
+        self.value = Some(value);
+        seed.deserialize(KeyDeserializer(key)).map(Some)
+    }
+
+    fn next_value_seed<V>(&mut self, seed: V) -> Result<V::Value, de::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        match self.value.take() {
+            Some(value) => seed.deserialize(value),
+            None => Err(de::Error::custom("value requested before its key")),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.iter.len())
+    }
+}
+
+/// Map keys are always strings in a [`Value`], so numbers, bools and the parts
+/// of tuple and variant keys are parsed back out of them on request
+struct KeyDeserializer(String);
+
+macro_rules! deserialize_key {
+    ($fn_name:ident, $visitor:ident) => {
+        fn $fn_name<V>(self, visitor: V) -> Result<V::Value, de::Error>
+        where
+            V: Visitor<'de>,
+        {
+            match self.0.parse() {
+                Ok(v) => visitor.$visitor(v),
+                Err(_) => visitor.visit_string(self.0),
+            }
+        }
+    };
+}
+
+impl<'de> Deserializer<'de> for KeyDeserializer {
+    type Error = de::Error;
+
+    fn deserialize_any<V>(self, visitor: V) -> Result<V::Value, de::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_string(self.0)
+    }
+
+    deserialize_key!(deserialize_bool, visit_bool);
+    deserialize_key!(deserialize_i8, visit_i8);
+    deserialize_key!(deserialize_i16, visit_i16);
+    deserialize_key!(deserialize_i32, visit_i32);
+    deserialize_key!(deserialize_i64, visit_i64);
+    deserialize_key!(deserialize_i128, visit_i128);
+    deserialize_key!(deserialize_u8, visit_u8);
+    deserialize_key!(deserialize_u16, visit_u16);
+    deserialize_key!(deserialize_u32, visit_u32);
+    deserialize_key!(deserialize_u64, visit_u64);
+    deserialize_key!(deserialize_u128, visit_u128);
+    deserialize_key!(deserialize_f32, visit_f32);
+    deserialize_key!(deserialize_f64, visit_f64);
+    deserialize_key!(deserialize_char, visit_char);
+
+    fn deserialize_option<V>(self, visitor: V) -> Result<V::Value, de::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_some(self)
+    }
+
+    fn deserialize_newtype_struct<V>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, de::Error>
+    where
+        V: Visitor<'de>,
+    {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V>(self, visitor: V) -> Result<V::Value, de::Error>
+    where
+        V: Visitor<'de>,
+    {
+        if !self.0.starts_with('(') {
+            return visitor.visit_string(self.0);
+        }
+        let (_, parts) = key::composite_parts(&self.0)?;
+        visitor.visit_seq(SeqDeserializer(parts.into_iter()))
+    }
+
+    fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value, de::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V>(
+        self,
+        _name: &'static str,
+        _len: usize,
+        visitor: V,
+    ) -> Result<V::Value, de::Error>
+    where
+        V: Visitor<'de>,
+    {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_enum<V>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, de::Error>
+    where
+        V: Visitor<'de>,
+    {
+        if !self.0.ends_with(')') {
+            return visitor.visit_enum(EnumDeserializer {
+                variant: self.0,
+                value: None,
+            });
+        }
+        // A newtype variant is written with its one part
+        let (variant, mut parts) = key::composite_parts(&self.0)?;
+        let value = match parts.len() {
+            1 => parts.pop(),
+            _ => Some(Value::Seq(parts)),
+        };
+        visitor.visit_enum(EnumDeserializer {
+            variant: variant.unwrap_or_default(),
+            value,
+        })
+    }
+
+    serde_core::forward_to_deserialize_any! {
+        str string bytes byte_buf unit unit_struct map struct identifier ignored_any
+    }
+}
+
+struct EnumDeserializer {
+    variant: String,
+    value: Option<Value>,
+}
+
+impl<'de> EnumAccess<'de> for EnumDeserializer {
+    type Error = de::Error;
+    type Variant = VariantDeserializer;
+
+    fn variant_seed<V>(self, seed: V) -> Result<(V::Value, VariantDeserializer), de::Error>
+    where
+        V: DeserializeSeed<'de>,
+    {
+        let variant = seed.deserialize(KeyDeserializer(self.variant))?;
+        Ok((variant, VariantDeserializer(self.value)))
+    }
+}
+
+struct VariantDeserializer(Option<Value>);
+
+impl<'de> VariantAccess<'de> for VariantDeserializer {
+    type Error = de::Error;
+
+    fn unit_variant(self) -> Result<(), de::Error> {
+        match self.0 {
+            None | Some(Value::Unit) | Some(Value::None) => Ok(()),
+            Some(value) => Err(value.invalid_type(&"unit variant")),
+        }
+    }
+
+    fn newtype_variant_seed<T>(self, seed: T) -> Result<T::Value, de::Error>
+    where
+        T: DeserializeSeed<'de>,
+    {
+        match self.0 {
+            Some(value) => seed.deserialize(value),
+            None => Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"newtype variant",
+            )),
+        }
+    }
+
+    fn tuple_variant<V>(self, _len: usize, visitor: V) -> Result<V::Value, de::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Some(Value::Seq(seq)) => visitor.visit_seq(SeqDeserializer(seq.into_iter())),
+            Some(value) => Err(value.invalid_type(&"tuple variant")),
+            None => Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"tuple variant",
+            )),
+        }
+    }
+
+    fn struct_variant<V>(
+        self,
+        _fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, de::Error>
+    where
+        V: Visitor<'de>,
+    {
+        match self.0 {
+            Some(Value::Map(map)) => visitor.visit_map(MapDeserializer {
+                iter: map.into_iter(),
+                value: None,
+            }),
+            Some(value) => Err(value.invalid_type(&"struct variant")),
+            None => Err(de::Error::invalid_type(
+                Unexpected::UnitVariant,
+                &"struct variant",
+            )),
+        }
+    }
+}